@@ -3,6 +3,10 @@ use bevy_egui::{egui, EguiContexts};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
+use crate::player::components::Player;
+use crate::world::lighting::DayNightClock;
+use crate::world::simulation::PixelSimulation;
+use crate::world::{PixelWorld, TerrainGenerator, WorldSeed};
 
 pub struct DebugPlugin;
 
@@ -19,7 +23,7 @@ impl Plugin for DebugPlugin {
 }
 
 /// Persistent debug configuration
-#[derive(Resource, Serialize, Deserialize, Clone)]
+#[derive(Resource, Serialize, Deserialize, Clone, Default)]
 pub struct DebugConfig {
     /// Show physics debug rendering (Rapier colliders)
     pub show_physics_debug: bool,
@@ -27,15 +31,6 @@ pub struct DebugConfig {
     pub show_fps: bool,
 }
 
-impl Default for DebugConfig {
-    fn default() -> Self {
-        Self {
-            show_physics_debug: false,
-            show_fps: false,
-        }
-    }
-}
-
 impl DebugConfig {
     const CONFIG_FILE: &'static str = "debug_config.json";
 
@@ -70,20 +65,17 @@ fn handle_debug_input(
     }
 }
 
-#[derive(Resource)]
+#[derive(Resource, Default)]
 pub struct DebugMenuVisible(pub bool);
 
-impl Default for DebugMenuVisible {
-    fn default() -> Self {
-        Self(false)
-    }
-}
-
 /// Draw the debug menu using egui
 fn draw_debug_menu(
     mut contexts: EguiContexts,
     menu_visible: Res<DebugMenuVisible>,
     mut debug_config: ResMut<DebugConfig>,
+    (seed, generator, world): (Res<WorldSeed>, Res<TerrainGenerator>, Res<PixelWorld>),
+    player: Res<Player>,
+    (mut simulation, mut clock): (ResMut<PixelSimulation>, ResMut<DayNightClock>),
 ) {
     if !menu_visible.0 {
         return;
@@ -112,6 +104,14 @@ fn draw_debug_menu(
 
             ui.add_space(10.0);
             ui.separator();
+            ui.label(format!("World seed: {}", seed.0));
+            ui.label(format!("Biome: {}", generator.biome_at(player.x as i32).name));
+            ui.label(format!("Awake chunks: {} / {}", world.awake_chunk_count(), world.chunks().count()));
+            ui.label(format!("Simulation tick: {:.2} ms", simulation.last_tick.as_secs_f64() * 1000.0));
+            ui.checkbox(&mut simulation.multithreaded, "Multithreaded simulation");
+            let (hours, minutes) = clock.hours_minutes();
+            ui.label(format!("Time: {:02}:{:02}{}", hours, minutes, if clock.is_night() { " (night)" } else { "" }));
+            ui.add(egui::Slider::new(&mut clock.time, 0.0..=1.0).text("Time of day"));
             ui.label("Press ` to close");
         });
 }
@@ -6,18 +6,19 @@ mod physics;
 mod tools;
 mod ui;
 mod debug;
+mod save;
 
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use bevy_egui::EguiPlugin;
-use constants::{WORLD_PIXEL_WIDTH, WORLD_PIXEL_HEIGHT};
+use constants::{WINDOW_WIDTH, WINDOW_HEIGHT};
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()).set(WindowPlugin {
             primary_window: Some(Window {
                 title: "Jungle Survival - Pixel Physics".to_string(),
-                resolution: (WORLD_PIXEL_WIDTH as f32, WORLD_PIXEL_HEIGHT as f32).into(),
+                resolution: (WINDOW_WIDTH, WINDOW_HEIGHT).into(),
                 ..default()
             }),
             ..default()
@@ -32,5 +33,6 @@ fn main() {
         .add_plugins(tools::ToolsPlugin)
         .add_plugins(ui::UiPlugin)
         .add_plugins(debug::DebugPlugin)
+        .add_plugins(save::SavePlugin)
         .run();
 }
@@ -0,0 +1,150 @@
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use super::collider_update::{body_mass, pixel_mass};
+use super::components::PixelBody;
+use super::pixel_displacement::RestingOnPowder;
+use crate::world::{PixelWorld, WorldService};
+
+/// How much of a submerged pixel's speed through the liquid it loses per second
+const LIQUID_DRAG: f32 = 3.0;
+
+type FloatingBody<'a> = (Entity, &'a Transform, &'a Velocity, &'a PixelBody, Option<&'a GravityScale>, &'a mut ExternalForce);
+
+/// Float bodies in liquids and slow them down there. Each body pixel under the surface is
+/// pushed up by the weight of the liquid it displaces and dragged against its own motion
+/// through the liquid, both applied where the pixel is, so a log tips until it lies flat
+/// and stops spinning. Wood is lighter than water and floats; dirt and stone sink.
+///
+/// Bodies resting on powder are held up by it too, since powder has no collider: it
+/// takes whatever of their weight the liquid doesn't.
+pub fn float_in_liquids(
+    mut commands: Commands,
+    world: Res<PixelWorld>,
+    resting: Res<RestingOnPowder>,
+    config_query: Query<&RapierConfiguration>,
+    new_bodies: Query<Entity, (With<PixelBody>, Without<ExternalForce>)>,
+    mut body_query: Query<FloatingBody>,
+) {
+    // Forces are worked out from next frame on
+    for entity in new_bodies.iter() {
+        commands.entity(entity).insert(ExternalForce::default());
+    }
+
+    let Ok(config) = config_query.single() else {
+        return;
+    };
+    for (entity, transform, velocity, body, gravity_scale, mut external) in body_query.iter_mut() {
+        let gravity = config.gravity * gravity_scale.map_or(1.0, |scale| scale.0);
+        let (mut force, torque) = liquid_forces(&world, transform, velocity, body, gravity);
+        if resting.0.contains(&entity) {
+            force.y = force.y.max(-gravity.y * body_mass(body));
+        }
+        external.force = force;
+        external.torque = torque;
+    }
+}
+
+/// Buoyancy and drag on the submerged part of a body, as a force and a torque about its
+/// centre of mass
+fn liquid_forces(world: &PixelWorld, transform: &Transform, velocity: &Velocity, body: &PixelBody, gravity: Vec2) -> (Vec2, f32) {
+    let center = transform.transform_point(body.local_center_of_mass().extend(0.0)).truncate();
+    let footprint = body.footprint(transform);
+
+    // Pixels in a stretch of a column of the body with liquid right above or below it
+    let mut columns: Vec<(i32, i32)> = footprint.iter().map(|(x, y, _)| (*x, *y)).collect();
+    columns.sort_unstable();
+    let mut wet_columns = HashSet::new();
+    for stretch in columns.chunk_by(|a, b| a.0 == b.0 && a.1 + 1 == b.1) {
+        let (x, top, bottom) = (stretch[0].0, stretch[0].1, stretch[stretch.len() - 1].1);
+        if world.get(x, top - 1).is_liquid() || world.get(x, bottom + 1).is_liquid() {
+            wet_columns.extend(stretch.iter().copied());
+        }
+    }
+
+    let mut force = Vec2::ZERO;
+    let mut torque = 0.0;
+    // Bodies push liquid out of the pixels they cover, so a pixel is under the surface when
+    // liquid reaches it along its row. That liquid has to be around it rather than off to
+    // one side - at both ends of the row, or above or below the body in its column - so a
+    // log on dry ground with a puddle at one end isn't lifted as if it lay in a pond.
+    for run in footprint.chunk_by(|a, b| a.1 == b.1 && a.0 + 1 == b.0) {
+        let (first, last) = (run[0], run[run.len() - 1]);
+        let ends = [world.get(first.0 - 1, first.1), world.get(last.0 + 1, last.1)];
+        let Some(liquid) = ends.into_iter().find(|material| material.is_liquid()) else {
+            continue;
+        };
+        let surrounded = ends.iter().all(|material| material.is_liquid());
+
+        for &(x, y, (_, _, material)) in run {
+            if !surrounded && !wet_columns.contains(&(x, y)) {
+                continue;
+            }
+            let mass = pixel_mass(material);
+            let offset = WorldService::pixel_center_to_world(x, y) - center;
+            let lift = -gravity * mass * liquid.props().fluid_density / material.props().fluid_density;
+            let pixel_velocity = velocity.linvel + velocity.angvel * offset.perp();
+            let drag = -pixel_velocity * mass * LIQUID_DRAG;
+            force += lift + drag;
+            torque += offset.perp_dot(lift + drag);
+        }
+    }
+    (force, torque)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::pixel_world::test_support::{fill, world_with_ground};
+    use crate::world::Material;
+
+    const GRAVITY: Vec2 = Vec2::new(0.0, -9.81 * 5.0);
+
+    /// Force on a still body of `material` filling `rect`, cleared out of the world the way
+    /// bodies push liquid aside, along with the body's weight
+    fn lift_on(world: &mut PixelWorld, rect: IRect, material: Material) -> (Vec2, f32, f32) {
+        fill(world, rect, Material::AIR);
+        let body = PixelBody::new(
+            (rect.min.y..rect.max.y)
+                .flat_map(|y| (rect.min.x..rect.max.x).map(move |x| (x, y, material)))
+                .collect(),
+        );
+        let position = WorldService::pixel_center_to_world(body.origin.x, body.origin.y);
+        let transform = Transform::from_translation(position.extend(1.0));
+        let weight = -GRAVITY.y * body.pixels.iter().map(|(_, _, m)| pixel_mass(*m)).sum::<f32>();
+        let (force, torque) = liquid_forces(world, &transform, &Velocity::zero(), &body, GRAVITY);
+        (force, torque, weight)
+    }
+
+    #[test]
+    fn wood_floats_and_stone_sinks() {
+        let mut world = world_with_ground(3, Material::DIRT);
+        fill(&mut world, IRect::new(0, 560, 96, 600), Material::WATER);
+
+        let (force, _, weight) = lift_on(&mut world, IRect::new(5, 575, 45, 581), Material::WOOD);
+        assert!(force.y > weight);
+        let (force, _, weight) = lift_on(&mut world, IRect::new(55, 575, 90, 581), Material::STONE);
+        assert!(force.y > 0.0 && force.y < weight);
+    }
+
+    #[test]
+    fn puddle_at_one_end_does_not_lift_a_log_on_dry_ground() {
+        let mut world = world_with_ground(3, Material::DIRT);
+        fill(&mut world, IRect::new(86, 596, 92, 600), Material::WATER);
+
+        let (force, torque, _) = lift_on(&mut world, IRect::new(10, 594, 86, 600), Material::WOOD);
+        assert_eq!(force, Vec2::ZERO);
+        assert_eq!(torque, 0.0);
+    }
+
+    #[test]
+    fn log_lying_in_a_shallow_stream_is_lifted_by_its_wet_rows() {
+        let mut world = world_with_ground(3, Material::DIRT);
+        fill(&mut world, IRect::new(0, 596, 96, 600), Material::WATER);
+
+        let (force, _, weight) = lift_on(&mut world, IRect::new(20, 594, 60, 600), Material::WOOD);
+        assert!(force.y > 0.0);
+        // Four of its six rows are under the surface
+        assert!((force.y - weight * 4.0 / 6.0 / 0.6).abs() < 1.0);
+    }
+}
@@ -0,0 +1,211 @@
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use super::components::PixelBody;
+use crate::world::{PixelWorld, Material, WorldService, ParticleSpawnEvent, WorldSeed};
+use crate::world::terrain::feature_hash;
+use crate::world::fire::{self, SPARK_CHANCE};
+
+/// Burn timers (seconds remaining) for the ember pixels of a chunk that has caught fire
+#[derive(Component, Default)]
+pub struct Burning {
+    pub timers: HashMap<(i32, i32), f32>,
+}
+
+/// Chance per second that a burning chunk pixel licks a flame into the world
+const CHUNK_FLAME_RATE: f64 = 3.0;
+
+/// Salt for each body's random stream
+const BURNING_SALT: u64 = 0xB0D1;
+
+/// Convert a per-tick (60/s) chance into a chance for this frame
+fn chance_this_frame(per_tick: f64, dt: f32) -> f64 {
+    let ticks = dt as f64 * 60.0;
+    (1.0 - (1.0 - per_tick).powf(ticks)).clamp(0.0, 1.0)
+}
+
+/// Burn pixels inside rigid body chunks: catch fire from flames in the world,
+/// spread between neighbouring pixels and remove embers once they burn out
+pub fn burn_pixel_bodies(
+    mut commands: Commands,
+    mut world: ResMut<PixelWorld>,
+    mut chunk_query: Query<(Entity, &Transform, &mut PixelBody, Option<&mut Burning>)>,
+    mut particle_events: EventWriter<ParticleSpawnEvent>,
+    seed: Res<WorldSeed>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+    // Flames land in the pixel world, so like the simulation each body gets its own
+    // random stream from the seed and tick, keyed by its origin. The maps below hash
+    // the same way every run, so pixels draw from the stream in the same order.
+    let tick_seed = feature_hash(seed.0, BURNING_SALT, world.tick() as i64);
+
+    for (entity, transform, mut chunk, burning) in chunk_query.iter_mut() {
+        if chunk.pixels.is_empty() {
+            continue;
+        }
+        let body_seed = feature_hash(tick_seed, BURNING_SALT, chunk.origin.x as i64);
+        let mut rng = StdRng::seed_from_u64(feature_hash(body_seed, BURNING_SALT, chunk.origin.y as i64));
+
+        let chunk_pos = transform.translation.truncate();
+        let rotation = transform.rotation.to_euler(EulerRot::XYZ).2;
+        let cos = rotation.cos();
+        let sin = rotation.sin();
+
+        let to_world = |px: i32, py: i32| {
+            let offset = chunk.local_offset(px, py);
+            Vec2::new(
+                chunk_pos.x + offset.x * cos - offset.y * sin,
+                chunk_pos.y + offset.x * sin + offset.y * cos,
+            )
+        };
+
+        let materials: HashMap<(i32, i32), Material> = chunk.pixels
+            .iter()
+            .map(|(x, y, m)| ((*x, *y), *m))
+            .collect();
+
+        // Catch fire from flames and embers in the pixel world around the chunk
+        let mut newly_lit: HashSet<(i32, i32)> = HashSet::new();
+        for (&(px, py), material) in materials.iter() {
+            if !material.is_flammable() {
+                continue;
+            }
+            let (wx, wy) = WorldService::world_to_pixel(to_world(px, py));
+            let touching_fire = [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .any(|(dx, dy)| matches!(world.get(wx + dx, wy + dy), Material::FIRE | Material::EMBER));
+            if touching_fire && rng.gen_bool(chance_this_frame(material.flammability(), dt)) {
+                newly_lit.insert((px, py));
+            }
+        }
+
+        let has_embers = materials.values().any(|m| *m == Material::EMBER);
+        if burning.is_none() && newly_lit.is_empty() && !has_embers {
+            continue;
+        }
+
+        let had_component = burning.is_some();
+        let mut new_timers = HashMap::new();
+        let timers = match burning {
+            Some(burning) => &mut burning.into_inner().timers,
+            None => &mut new_timers,
+        };
+
+        // Embers without a timer were burning before the chunk broke off or split
+        for (&pos, material) in materials.iter() {
+            if *material == Material::EMBER {
+                timers.entry(pos).or_insert(Material::WOOD.burn_time() as f32 / 60.0);
+            }
+        }
+
+        // Spread between neighbouring pixels inside the chunk
+        let embers: Vec<(i32, i32)> = timers.keys().copied().collect();
+        for &(px, py) in &embers {
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let neighbor = (px + dx, py + dy);
+                if let Some(material) = materials.get(&neighbor) {
+                    if material.is_flammable()
+                        && rng.gen_bool(chance_this_frame(material.flammability(), dt))
+                    {
+                        newly_lit.insert(neighbor);
+                    }
+                }
+            }
+        }
+
+        for pos in &newly_lit {
+            if let Some(material) = materials.get(pos) {
+                timers.insert(*pos, material.burn_time() as f32 / 60.0);
+            }
+        }
+
+        // Burn down, throwing flames into the world and sparks into the air
+        let mut burnt_out = HashSet::new();
+        for (&(px, py), timer) in timers.iter_mut() {
+            *timer -= dt;
+            if *timer <= 0.0 {
+                burnt_out.insert((px, py));
+                continue;
+            }
+
+            let world_pos = to_world(px, py);
+            if rng.gen_bool((CHUNK_FLAME_RATE * dt as f64).min(1.0)) {
+                let (wx, wy) = WorldService::world_to_pixel(world_pos + Vec2::Y);
+                fire::spawn_flame(&mut *world, &mut rng, wx, wy);
+            }
+            if rng.gen_bool(chance_this_frame(SPARK_CHANCE, dt)) {
+                particle_events.write(ParticleSpawnEvent {
+                    position: world_pos,
+                    material: Material::EMBER,
+                });
+            }
+        }
+        timers.retain(|pos, _| !burnt_out.contains(pos));
+
+        // Only touch the chunk when pixels changed so colliders aren't rebuilt every frame
+        if !newly_lit.is_empty() || !burnt_out.is_empty() {
+            let pixels = chunk.pixels
+                .iter()
+                .filter(|(x, y, _)| !burnt_out.contains(&(*x, *y)))
+                .map(|(x, y, m)| {
+                    if newly_lit.contains(&(*x, *y)) {
+                        (*x, *y, Material::EMBER)
+                    } else {
+                        (*x, *y, *m)
+                    }
+                })
+                .collect();
+            chunk.pixels = pixels;
+        }
+
+        if !had_component {
+            commands.entity(entity).insert(Burning { timers: new_timers });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::pixel_world::test_support::empty_world;
+    use std::time::Duration;
+
+    /// Burn a wood log lying on a row of embers for a second and return the world
+    /// around it afterwards
+    fn burn_log() -> Vec<Material> {
+        let mut world = empty_world(4);
+        for x in 10..110 {
+            world.set(x, 600, Material::EMBER);
+        }
+        let mut app = App::new();
+        app.insert_resource(world)
+            .insert_resource(WorldSeed(7))
+            .init_resource::<Time>()
+            .add_event::<ParticleSpawnEvent>()
+            .add_systems(Update, burn_pixel_bodies);
+
+        let log = PixelBody::new((10..110).flat_map(|x| (594..600).map(move |y| (x, y, Material::WOOD))).collect());
+        let position = WorldService::pixel_center_to_world(log.origin.x, log.origin.y);
+        app.world_mut().spawn((Transform::from_translation(position.extend(1.0)), log));
+        for _ in 0..60 {
+            app.world_mut().resource_mut::<Time>().advance_by(Duration::from_millis(17));
+            app.update();
+        }
+
+        let mut query = app.world_mut().query::<&PixelBody>();
+        assert!(query.single(app.world()).unwrap().pixels.iter().any(|(_, _, m)| *m == Material::EMBER));
+        app.world().resource::<PixelWorld>().materials_in(IRect::new(0, 560, 128, 620))
+    }
+
+    #[test]
+    fn burning_bodies_light_the_same_flames_every_run() {
+        let first = burn_log();
+        assert!(first.contains(&Material::FIRE));
+        assert_eq!(first, burn_log());
+    }
+}
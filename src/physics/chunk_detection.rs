@@ -1,132 +1,214 @@
+use bevy::platform::collections::HashSet;
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
-use crate::world::{PixelWorld, Material, WorldService};
-use crate::constants::CHUNK_DETECTION_INTERVAL;
-use super::components::WoodChunk;
-use std::collections::{HashSet, VecDeque};
+use crate::world::materials::{MaterialProperties, MaterialState, Unsupported};
+use crate::world::{Material, MaterialRegistry, ParticleSpawnEvent, PixelWorld, WorldService};
+use super::collider_update::{chunk_collider, chunk_mass};
+use super::components::PixelBody;
+use std::collections::{BinaryHeap, VecDeque};
 
-/// Check for wood that's disconnected from ground and convert to rigid bodies
+/// Smallest cluster that falls as a body; smaller bits break up into particles
+const MIN_BODY_PIXELS: usize = 5;
+
+/// Areas with more ground than this are left where they are - they're floating islands
+/// the terrain was generated with rather than something that just broke off. Searches for
+/// support give up once they've reached this much ground.
+const MAX_FALLING_GROUND: usize = 5000;
+
+/// Searches through anything else give up past this, far beyond the biggest tree
+const MAX_SEARCH_PIXELS: usize = 100_000;
+
+const NEIGHBOURS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+/// Check whether anything next to the weight-carrying pixels removed since the last pass
+/// has lost its support, and let unsupported solids go the way their material says:
+/// fall as rigid bodies or crumble into powder. Only the area around removed pixels is
+/// searched, so cutting one pixel costs a short walk to the ground rather than the world.
 pub fn detect_floating_chunks(
     mut commands: Commands,
     mut world: ResMut<PixelWorld>,
-    time: Res<Time>,
+    mut particle_events: EventWriter<ParticleSpawnEvent>,
 ) {
-    // Check more frequently for faster response
-    if time.elapsed_secs() % CHUNK_DETECTION_INTERVAL > 0.02 {
+    let removed = world.take_support_lost();
+    if removed.is_empty() {
         return;
     }
 
-    let width = world.width as i32;
-    let height = world.height as i32;
-
-    // Find all wood pixels that are grounded (connected to solid ground like dirt)
-    let mut grounded = HashSet::new();
-    let mut to_check = VecDeque::new();
-
-    // Find wood that's touching ground (dirt, sand, or other non-wood solid)
-    for y in 0..height {
-        for x in 0..width {
-            if world.get(x, y) == Material::Wood {
-                // Check if this wood is touching solid ground (non-air, non-wood)
-                let mut touches_ground = false;
-                for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
-                    let nx = x + dx;
-                    let ny = y + dy;
-                    let material = world.get(nx, ny);
-
-                    // Wood is grounded if it touches dirt or sand
-                    if material == Material::Dirt || material == Material::Sand {
-                        touches_ground = true;
-                        break;
-                    }
-                }
-
-                if touches_ground {
-                    grounded.insert((x, y));
-                    to_check.push_back((x, y));
+    let roles: Vec<SupportRole> = MaterialRegistry::global().iter().map(|(_, props)| support_role(props)).collect();
+    let role = |material: Material| roles.get(material.0 as usize).copied().unwrap_or(SupportRole::Open);
+
+    // Everything a search has reached this pass. Searches that found support mark the
+    // pixels they went through, so later ones can stop as soon as they meet them.
+    let mut checked = HashSet::new();
+    // Searches that gave up aren't repeated from anywhere they went through
+    let mut gave_up = HashSet::new();
+    let mut clusters = Vec::new();
+    for (x, y) in removed {
+        for (dx, dy) in NEIGHBOURS {
+            let start = (x + dx, y + dy);
+            if checked.contains(&start)
+                || gave_up.contains(&start)
+                || role(world.get(start.0, start.1)) == SupportRole::Open
+            {
+                continue;
+            }
+            match find_support(&world, &role, start, &checked) {
+                Search::Supported(reached) => checked.extend(reached),
+                Search::Unsupported(reached) => {
+                    checked.extend(reached.iter().copied());
+                    clusters.extend(solid_clusters(&world, &role, reached));
                 }
+                Search::GaveUp(reached) => gave_up.extend(reached),
             }
         }
     }
 
-    // Flood fill to find all grounded wood and leaves
-    while let Some((x, y)) = to_check.pop_front() {
-        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
-            let nx = x + dx;
-            let ny = y + dy;
-
-            if nx >= 0 && nx < width && ny >= 0 && ny < height {
-                let material = world.get(nx, ny);
-                // Include both wood and leaves in the grounded check
-                if (material == Material::Wood || material == Material::Leaf) && !grounded.contains(&(nx, ny)) {
-                    grounded.insert((nx, ny));
-                    to_check.push_back((nx, ny));
-                }
-            }
-        }
+    for cluster in clusters {
+        release_cluster(&mut commands, &mut world, &mut particle_events, cluster);
     }
+}
 
-    // Find floating wood chunks (including attached leaves)
-    let mut visited = HashSet::new();
-    let mut chunks_to_spawn = Vec::new();
+/// How a material takes part in holding other pixels up
+#[derive(Clone, Copy, PartialEq)]
+enum SupportRole {
+    /// Holds nothing up - air, liquids, gases
+    Open,
+    /// Passes support on but is never let go here, since it falls by itself
+    Loose,
+    /// Passes support on, and breaks away when it has none
+    Solid,
+    /// Holds things up wherever it is
+    Anchor,
+}
 
-    for y in 0..height {
-        for x in 0..width {
-            let material = world.get(x, y);
-            if (material == Material::Wood || material == Material::Leaf)
-                && !grounded.contains(&(x, y))
-                && !visited.contains(&(x, y))
-            {
-                // Found a floating chunk - flood fill to get all connected pixels (wood + leaves)
-                let chunk_pixels = flood_fill_chunk(&world, x, y, &mut visited);
+fn support_role(props: &MaterialProperties) -> SupportRole {
+    match props.state {
+        MaterialState::Powder => SupportRole::Loose,
+        MaterialState::Solid if props.unsupported == Unsupported::Stays => SupportRole::Anchor,
+        MaterialState::Solid => SupportRole::Solid,
+        _ => SupportRole::Open,
+    }
+}
 
-                if chunk_pixels.len() >= 5 {
-                    // Only spawn chunks with at least 5 pixels
-                    chunks_to_spawn.push(chunk_pixels);
-                }
+/// What a search for support found, with every pixel it reached
+enum Search {
+    Supported(Vec<(i32, i32)>),
+    Unsupported(Vec<(i32, i32)>),
+    /// Too big to be something that just broke off, so it's left as it is
+    GaveUp(Vec<(i32, i32)>),
+}
+
+/// Search out from `start` through everything that carries weight, deepest pixels first,
+/// for something holding it up: the bottom row of the world, a material that stays put,
+/// an unloaded chunk we can't see into, or a pixel an earlier search found supported.
+fn find_support(
+    world: &PixelWorld,
+    role: &impl Fn(Material) -> SupportRole,
+    start: (i32, i32),
+    checked: &HashSet<(i32, i32)>,
+) -> Search {
+    let mut reached = vec![start];
+    let mut seen = HashSet::from([start]);
+    let mut to_check = BinaryHeap::from([(start.1, start.0)]);
+    let mut ground = 0;
+
+    while let Some((y, x)) = to_check.pop() {
+        let material = world.get(x, y);
+        if y == world.height - 1 || role(material) == SupportRole::Anchor {
+            return Search::Supported(reached);
+        }
+        if material.props().ground {
+            ground += 1;
+        }
+        if ground > MAX_FALLING_GROUND || reached.len() > MAX_SEARCH_PIXELS {
+            return Search::GaveUp(reached);
+        }
+        for (dx, dy) in NEIGHBOURS {
+            let (nx, ny) = (x + dx, y + dy);
+            if seen.contains(&(nx, ny)) {
+                continue;
+            }
+            if checked.contains(&(nx, ny)) || world.is_unloaded(nx, ny) {
+                return Search::Supported(reached);
+            }
+            if role(world.get(nx, ny)) != SupportRole::Open {
+                seen.insert((nx, ny));
+                reached.push((nx, ny));
+                to_check.push((ny, nx));
             }
         }
     }
 
-    // Spawn rigid bodies for each chunk
-    for chunk_pixels in chunks_to_spawn {
-        spawn_wood_chunk(&mut commands, &mut world, chunk_pixels);
-    }
+    Search::Unsupported(reached)
 }
 
-fn flood_fill_chunk(
+/// Split the solids in an unsupported area into 4-connected clusters, so pieces only
+/// joined through powder fall separately. Powders are left to the simulation, which
+/// already lets them fall.
+fn solid_clusters(
     world: &PixelWorld,
-    start_x: i32,
-    start_y: i32,
-    visited: &mut HashSet<(i32, i32)>,
-) -> Vec<(i32, i32, Material)> {
-    let mut chunk = Vec::new();
-    let mut to_check = VecDeque::new();
-
-    to_check.push_back((start_x, start_y));
-    visited.insert((start_x, start_y));
-
-    while let Some((x, y)) = to_check.pop_front() {
-        let material = world.get(x, y);
-        chunk.push((x, y, material));
-
-        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
-            let nx = x + dx;
-            let ny = y + dy;
+    role: &impl Fn(Material) -> SupportRole,
+    area: Vec<(i32, i32)>,
+) -> Vec<Vec<(i32, i32, Material)>> {
+    let mut solids: HashSet<(i32, i32)> = area
+        .into_iter()
+        .filter(|(x, y)| role(world.get(*x, *y)) == SupportRole::Solid)
+        .collect();
+
+    let mut clusters = Vec::new();
+    while let Some(&start) = solids.iter().next() {
+        solids.remove(&start);
+        let mut cluster = Vec::new();
+        let mut to_check = VecDeque::from([start]);
+        while let Some((x, y)) = to_check.pop_front() {
+            cluster.push((x, y, world.get(x, y)));
+            for (dx, dy) in NEIGHBOURS {
+                if solids.remove(&(x + dx, y + dy)) {
+                    to_check.push_back((x + dx, y + dy));
+                }
+            }
+        }
+        clusters.push(cluster);
+    }
+    clusters
+}
 
-            let neighbor_material = world.get(nx, ny);
-            // Include both wood and leaves in the chunk
-            if (neighbor_material == Material::Wood || neighbor_material == Material::Leaf) && !visited.contains(&(nx, ny)) {
-                visited.insert((nx, ny));
-                to_check.push_back((nx, ny));
+/// Let an unsupported cluster go: crumbling materials turn to powder where they are,
+/// the rest falls as one body, or scatters as particles if there's too little of it
+fn release_cluster(
+    commands: &mut Commands,
+    world: &mut PixelWorld,
+    particle_events: &mut EventWriter<ParticleSpawnEvent>,
+    cluster: Vec<(i32, i32, Material)>,
+) {
+    let registry = MaterialRegistry::global();
+    let mut falling = Vec::new();
+    for (x, y, material) in cluster {
+        match material.props().unsupported {
+            Unsupported::Crumbles => {
+                if let Some(loose) = registry.crumbles_into(material) {
+                    world.set(x, y, loose);
+                }
             }
+            _ => falling.push((x, y, material)),
+        }
+    }
+
+    if falling.len() < MIN_BODY_PIXELS {
+        for (x, y, material) in falling {
+            world.set(x, y, Material::AIR);
+            particle_events.write(ParticleSpawnEvent {
+                position: WorldService::pixel_to_world(x, y),
+                material,
+            });
         }
+        return;
     }
 
-    chunk
+    spawn_pixel_body(commands, world, falling);
 }
 
-fn spawn_wood_chunk(
+pub(super) fn spawn_pixel_body(
     commands: &mut Commands,
     world: &mut PixelWorld,
     pixels: Vec<(i32, i32, Material)>,
@@ -135,18 +217,16 @@ fn spawn_wood_chunk(
         return;
     }
 
-    // Calculate center of mass
-    let sum_x: i32 = pixels.iter().map(|(x, _, _)| x).sum();
-    let sum_y: i32 = pixels.iter().map(|(_, y, _)| y).sum();
-    let count = pixels.len() as i32;
-    let center_x = sum_x / count;
-    let center_y = sum_y / count;
+    // The body sits on the pixel nearest its centre of mass, weighted by density,
+    // so a felled palm pivots around its trunk rather than its leaves
+    let chunk = PixelBody::new(pixels);
+    let pixels = &chunk.pixels;
+    let (center_x, center_y) = (chunk.origin.x, chunk.origin.y);
 
-    // Convert pixel coordinates to world coordinates
-    let world_pos = WorldService::pixel_to_world(center_x, center_y);
+    // The transform sits on the origin pixel's centre, where its sprite and collider are centred
+    let world_pos = WorldService::pixel_center_to_world(center_x, center_y);
 
-    // Create convex hull approximation from pixels for collider
-    // For now, use simplified cuboid based on bounding box
+    // Bounding box, to tell tall trunks that should tip over from other shapes
     let min_x = pixels.iter().map(|(x, _, _)| *x).min().unwrap();
     let max_x = pixels.iter().map(|(x, _, _)| *x).max().unwrap();
     let min_y = pixels.iter().map(|(_, y, _)| *y).min().unwrap();
@@ -193,15 +273,16 @@ fn spawn_wood_chunk(
     }
 
     // Remove pixels from pixel world
-    for (x, y, _) in &pixels {
-        world.set(*x, *y, Material::Air);
+    for (x, y, _) in pixels {
+        world.set(*x, *y, Material::AIR);
     }
 
     // Spawn rigid body with realistic tree falling physics
     commands.spawn((
         Transform::from_xyz(world_pos.x, world_pos.y, 1.0),
         RigidBody::Dynamic,
-        Collider::cuboid(width / 2.0, height / 2.0),
+        chunk_collider(&chunk),
+        chunk_mass(&chunk),
         Velocity {
             linvel: initial_torque,
             angvel: angular_velocity,
@@ -213,8 +294,90 @@ fn spawn_wood_chunk(
             linear_damping: 0.1,
             angular_damping: 0.8,  // More damping to prevent excessive spinning
         },
-        WoodChunk {
-            pixels: pixels.clone(),
-        },
+        chunk,
     ));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::CHUNK_SIZE;
+    use crate::world::pixel_world::test_support::{fill, world_with_ground};
+
+    fn detection_app(world: PixelWorld) -> App {
+        let mut app = App::new();
+        app.insert_resource(world)
+            .add_event::<ParticleSpawnEvent>()
+            .add_systems(Update, detect_floating_chunks);
+        // Nothing has been cut yet
+        app.world_mut().resource_mut::<PixelWorld>().take_support_lost();
+        app
+    }
+
+    fn body_sizes(app: &mut App) -> Vec<usize> {
+        let mut query = app.world_mut().query::<&PixelBody>();
+        query.iter(app.world()).map(|body| body.pixels.len()).collect()
+    }
+
+    #[test]
+    fn tree_bigger_than_a_floating_island_falls_when_cut() {
+        let mut world = world_with_ground(6, Material::DIRT);
+        // A 16x140 trunk under a canopy, well over the ground limit for floating islands
+        fill(&mut world, IRect::new(40, 460, 56, 600), Material::WOOD);
+        fill(&mut world, IRect::new(10, 420, 170, 460), Material::LEAF);
+        let tree = 16 * 140 + 160 * 40;
+        let mut app = detection_app(world);
+
+        fill(&mut app.world_mut().resource_mut::<PixelWorld>(), IRect::new(40, 599, 56, 600), Material::AIR);
+        app.update();
+
+        assert_eq!(body_sizes(&mut app), vec![tree - 16]);
+    }
+
+    #[test]
+    fn floating_island_of_ground_stays() {
+        let mut world = world_with_ground(6, Material::DIRT);
+        fill(&mut world, IRect::new(0, 100, 6 * CHUNK_SIZE, 140), Material::DIRT);
+        let mut app = detection_app(world);
+
+        app.world_mut().resource_mut::<PixelWorld>().set(50, 139, Material::AIR);
+        app.update();
+
+        assert!(body_sizes(&mut app).is_empty());
+        assert_eq!(app.world().resource::<PixelWorld>().get(50, 138), Material::DIRT);
+    }
+
+    #[test]
+    fn tree_on_roots_stands_when_the_ground_under_them_is_dug_out() {
+        let mut world = world_with_ground(6, Material::DIRT);
+        fill(&mut world, IRect::new(40, 580, 56, 602), Material::ROOT);
+        fill(&mut world, IRect::new(44, 500, 52, 580), Material::WOOD);
+        let mut app = detection_app(world);
+
+        fill(&mut app.world_mut().resource_mut::<PixelWorld>(), IRect::new(30, 600, 66, 640), Material::AIR);
+        app.update();
+        assert!(body_sizes(&mut app).is_empty());
+
+        // Cutting the trunk off its roots still fells it
+        fill(&mut app.world_mut().resource_mut::<PixelWorld>(), IRect::new(44, 579, 52, 580), Material::AIR);
+        app.update();
+        assert_eq!(body_sizes(&mut app), vec![8 * 79]);
+    }
+
+    #[test]
+    fn undermined_sandstone_crumbles_into_sand() {
+        let mut world = world_with_ground(6, Material::DIRT);
+        // A ledge over a hollow, on a pillar
+        fill(&mut world, IRect::new(10, 560, 30, 600), Material::SANDSTONE);
+        fill(&mut world, IRect::new(30, 560, 100, 570), Material::SANDSTONE);
+        let mut app = detection_app(world);
+
+        fill(&mut app.world_mut().resource_mut::<PixelWorld>(), IRect::new(10, 599, 30, 600), Material::AIR);
+        app.update();
+
+        assert!(body_sizes(&mut app).is_empty());
+        let world = app.world().resource::<PixelWorld>();
+        assert_eq!(world.get(20, 580), Material::SAND);
+        assert_eq!(world.get(90, 565), Material::SAND);
+    }
+}
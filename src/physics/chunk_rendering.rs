@@ -1,48 +1,71 @@
 use bevy::prelude::*;
-use super::components::WoodChunk;
-use crate::world::Material;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::sprite::Anchor;
+use super::components::PixelBody;
+use crate::world::chunk::pixel_shade;
 
-pub fn render_wood_chunks(
-    chunk_query: Query<(&WoodChunk, &Transform)>,
-    mut gizmos: Gizmos,
+/// Bake each wood chunk's pixels into an image shown by a sprite on the chunk's entity,
+/// so it follows the rigid body's transform. Only chunks whose pixels changed are rebaked.
+pub fn bake_pixel_bodies(
+    mut commands: Commands,
+    mut chunk_query: Query<(Entity, &PixelBody, Option<&mut Sprite>), Changed<PixelBody>>,
+    mut images: ResMut<Assets<Image>>,
 ) {
-    for (chunk, transform) in chunk_query.iter() {
-        // Calculate center offset
-        let sum_x: i32 = chunk.pixels.iter().map(|(x, _, _)| x).sum();
-        let sum_y: i32 = chunk.pixels.iter().map(|(_, y, _)| y).sum();
-        let count = chunk.pixels.len() as i32;
-        let center_x = sum_x / count;
-        let center_y = sum_y / count;
+    for (entity, chunk, sprite) in chunk_query.iter_mut() {
+        if chunk.pixels.is_empty() {
+            continue;
+        }
 
-        // Get rotation from transform (z rotation for 2D)
-        let rotation_angle = transform.rotation.to_euler(EulerRot::XYZ).2;
+        let min_x = chunk.pixels.iter().map(|(x, _, _)| *x).min().unwrap();
+        let max_x = chunk.pixels.iter().map(|(x, _, _)| *x).max().unwrap();
+        let min_y = chunk.pixels.iter().map(|(_, y, _)| *y).min().unwrap();
+        let max_y = chunk.pixels.iter().map(|(_, y, _)| *y).max().unwrap();
+        let width = (max_x - min_x + 1) as u32;
+        let height = (max_y - min_y + 1) as u32;
 
-        // Draw each pixel as a small rectangle, rotated with the transform
+        // Image rows run top to bottom, the same as pixel y
+        let mut data = vec![0; (width * height * 4) as usize];
         for (px, py, material) in &chunk.pixels {
-            let offset_x = (*px - center_x) as f32;
-            let offset_y = -(*py - center_y) as f32; // Negative because screen y is flipped
-
-            // Apply rotation to the offset
-            let cos = rotation_angle.cos();
-            let sin = rotation_angle.sin();
-            let rotated_x = offset_x * cos - offset_y * sin;
-            let rotated_y = offset_x * sin + offset_y * cos;
-
-            let world_x = transform.translation.x + rotated_x;
-            let world_y = transform.translation.y + rotated_y;
+            // Pixels keep the coordinates they had in the world, so they get the same shade they had there
+            let color = material.props().shade_color(pixel_shade(*material, *px, *py));
+            let i = ((py - min_y) as u32 * width + (px - min_x) as u32) as usize * 4;
+            data[i..i + 4].copy_from_slice(&color.to_srgba().to_u8_array());
+        }
 
-            // Get color based on material
-            let color = match material {
-                Material::Wood => Color::srgb(0.5, 0.3, 0.15),
-                Material::Leaf => Color::srgb(0.2, 0.6, 0.2),
-                _ => Color::srgb(0.5, 0.3, 0.15), // Default to wood color
-            };
+        // The entity sits on the middle of the chunk's origin pixel, so anchor the sprite there.
+        // Anchors run from -0.5 to 0.5 across the sprite with y pointing up.
+        let anchor = Anchor::Custom(Vec2::new(
+            ((chunk.origin.x - min_x) as f32 + 0.5) / width as f32 - 0.5,
+            0.5 - ((chunk.origin.y - min_y) as f32 + 0.5) / height as f32,
+        ));
 
-            gizmos.rect_2d(
-                Isometry2d::new(Vec2::new(world_x, world_y), Rot2::radians(rotation_angle)),
-                Vec2::new(1.0, 1.0),
-                color,
-            );
+        // Reuse the image when the chunk's bounds didn't change, e.g. while it burns
+        if let Some(mut sprite) = sprite {
+            if let Some(image) = images.get_mut(&sprite.image) {
+                if image.width() == width && image.height() == height {
+                    image.data = Some(data);
+                    sprite.anchor = anchor;
+                    continue;
+                }
+            }
         }
+
+        let image = images.add(Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+        ));
+        commands.entity(entity).insert(Sprite {
+            image,
+            anchor,
+            ..default()
+        });
     }
 }
@@ -0,0 +1,272 @@
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use super::chunk_detection::spawn_pixel_body;
+use super::collider_update::body_mass;
+use super::components::PixelBody;
+use crate::tools::components::GrabbedChunk;
+use crate::world::chunk::pixel_shade;
+use crate::world::ground_colliders::GroundCollider;
+use crate::world::materials::MaterialState;
+use crate::world::{Material, MaterialRegistry, PixelWorld};
+
+/// Seconds a chunk has to lie still before it settles back into the pixel world
+const SETTLE_TIME: f32 = 1.0;
+
+/// Slower than this (pixels and radians per second) counts as lying still
+const SETTLE_SPEED: f32 = 2.0;
+const SETTLE_SPIN: f32 = 0.05;
+
+/// How far up a pixel may be pushed to get out of something already in the world
+const MAX_PUSH: i32 = 3;
+
+/// Deceleration (pixels per second squared) of a body hitting settled pixels that knocks
+/// them loose again
+pub const UNSETTLE_IMPACT: f32 = 1500.0;
+
+/// How far (in pixels) from a body settled pixels count as hit by it. Colliders are
+/// simplified outlines, so they can rest a pixel apart.
+const HIT_REACH: i32 = 2;
+
+/// A chunk pixel's place in the world: (x, y, material, shade)
+type SettledPixel = (i32, i32, Material, u8);
+
+/// How long a chunk has been lying still
+#[derive(Component, Default)]
+pub struct Resting {
+    pub time: f32,
+}
+
+/// Turn chunks that have come to rest back into pixels, so fallen trees become terrain
+/// that can be stood on, dug and built around instead of costing physics time forever.
+/// Pixels turn into their material's `settles_into` - wood into logs, leaves into litter -
+/// which is ground, so it gets a ground collider and holds up the player. Settled pixels
+/// that lose their support are picked up again by `detect_floating_chunks`, and ones hit
+/// hard by another body by `unsettle_hit_pixels`.
+pub fn settle_resting_chunks(
+    mut commands: Commands,
+    mut world: ResMut<PixelWorld>,
+    grabbed: Res<GrabbedChunk>,
+    mut chunk_query: Query<(Entity, &Transform, &Velocity, &PixelBody, Option<&mut Resting>)>,
+    time: Res<Time>,
+) {
+    for (entity, transform, velocity, chunk, resting) in chunk_query.iter_mut() {
+        let still = velocity.linvel.length() < SETTLE_SPEED
+            && velocity.angvel.abs() < SETTLE_SPIN
+            && grabbed.entity != Some(entity);
+
+        let Some(mut resting) = resting else {
+            if still {
+                commands.entity(entity).insert(Resting::default());
+            }
+            continue;
+        };
+        if !still {
+            resting.time = 0.0;
+            continue;
+        }
+        resting.time += time.delta_secs();
+        if resting.time < SETTLE_TIME || chunk.pixels.is_empty() {
+            continue;
+        }
+
+        // Try again later if it can't settle where it is, e.g. it's frozen over unloaded chunks
+        let Some(placed) = rasterise_chunk(&world, transform, chunk) else {
+            resting.time = 0.0;
+            continue;
+        };
+
+        for (x, y, material, shade) in &placed {
+            world.set(*x, *y, *material);
+            world.set_shade(*x, *y, *shade);
+        }
+        commands.entity(entity).despawn();
+        info!("Settled a chunk of {} pixels back into the world", placed.len());
+    }
+}
+
+/// Where a chunk's pixels land in the world at its current position and rotation.
+/// Pixels overlapping something already there are pushed up a little, or dropped if
+/// there's no room.
+///
+/// None if the body reaches into an unloaded chunk, too much of it doesn't fit, or nothing
+/// would hold it up - it would only break off again straight away. Bodies mostly made of
+/// something that wouldn't be ground, like embers, stay bodies so nothing can fall through them.
+fn rasterise_chunk(world: &PixelWorld, transform: &Transform, chunk: &PixelBody) -> Option<Vec<SettledPixel>> {
+    let registry = MaterialRegistry::global();
+    let ground = chunk.pixels.iter().filter(|(_, _, material)| registry.settles_into(*material).props().ground).count();
+    if ground * 2 < chunk.pixels.len() {
+        return None;
+    }
+
+    // Bottom up, so pixels pushed out of the way stack on the ones below them
+    let mut placed = Vec::new();
+    let mut taken = HashSet::new();
+    for (x, y, (source_x, source_y, material)) in chunk.footprint(transform) {
+        if world.is_unloaded(x, y) {
+            return None;
+        }
+
+        let free = (0..=MAX_PUSH).map(|push| (x, y - push)).find(|&(tx, ty)| {
+            world.in_bounds(tx, ty)
+                && !taken.contains(&(tx, ty))
+                && matches!(world.get(tx, ty).props().state, MaterialState::Empty | MaterialState::Gas)
+        });
+        if let Some((tx, ty)) = free {
+            taken.insert((tx, ty));
+            // Pixels keep the shade they had where they broke off
+            let settled = registry.settles_into(material);
+            placed.push((tx, ty, settled, pixel_shade(settled, source_x, source_y)));
+        }
+    }
+
+    if placed.len() * 2 < chunk.pixels.len() {
+        return None;
+    }
+
+    // Settled pixels have to rest on something that carries weight - terrain, or wood
+    // already standing in the world
+    let supported = placed.iter().any(|(x, y, _, _)| {
+        [(-1, 0), (1, 0), (0, -1), (0, 1)].iter().any(|(dx, dy)| {
+            let neighbor = (x + dx, y + dy);
+            !taken.contains(&neighbor) && world.get(neighbor.0, neighbor.1).is_solid()
+        })
+    });
+    supported.then_some(placed)
+}
+
+/// Knock settled pixels loose again when a body hits them hard, so a log dropped onto a
+/// fallen one sends it rolling instead of bouncing off it like bedrock. Everything settled
+/// and solid that's connected to where it was hit breaks off as one body.
+pub fn unsettle_hit_pixels(
+    mut commands: Commands,
+    mut world: ResMut<PixelWorld>,
+    mut contact_events: EventReader<ContactForceEvent>,
+    ground_query: Query<(), With<GroundCollider>>,
+    body_query: Query<(&Transform, &PixelBody)>,
+) {
+    let mut hits = Vec::new();
+    for event in contact_events.read() {
+        for (body, other) in [(event.collider1, event.collider2), (event.collider2, event.collider1)] {
+            let Ok((transform, chunk)) = body_query.get(body) else {
+                continue;
+            };
+            if ground_query.contains(other) && event.total_force_magnitude / body_mass(chunk) > UNSETTLE_IMPACT {
+                hits.push(chunk.footprint(transform));
+            }
+        }
+    }
+    if hits.is_empty() {
+        return;
+    }
+
+    let registry = MaterialRegistry::global();
+    let settled = |material: Material| registry.is_settled(material) && material.props().state == MaterialState::Solid;
+    for footprint in hits {
+        let own: HashSet<(i32, i32)> = footprint.iter().map(|(x, y, _)| (*x, *y)).collect();
+        let mut to_check: Vec<(i32, i32)> = own
+            .iter()
+            .flat_map(|(x, y)| {
+                (-HIT_REACH..=HIT_REACH).flat_map(move |dy| (-HIT_REACH..=HIT_REACH).map(move |dx| (x + dx, y + dy)))
+            })
+            .filter(|pixel| !own.contains(pixel))
+            .collect();
+
+        let mut seen = HashSet::new();
+        let mut pixels = Vec::new();
+        while let Some((x, y)) = to_check.pop() {
+            let material = world.get(x, y);
+            if !settled(material) || !seen.insert((x, y)) {
+                continue;
+            }
+            pixels.push((x, y, material));
+            to_check.extend([(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]);
+        }
+        if !pixels.is_empty() {
+            info!("A hit knocked {} settled pixels loose", pixels.len());
+            spawn_pixel_body(&mut commands, &mut world, pixels);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::ground_colliders::is_outlined;
+    use crate::world::pixel_world::test_support::world_with_ground;
+    use crate::world::WorldService;
+
+    /// A body of `material` filling `rect`, and the transform it has where it broke off
+    fn body(rect: IRect, material: impl Fn(i32, i32) -> Material) -> (Transform, PixelBody) {
+        let pixels = (rect.min.y..rect.max.y)
+            .flat_map(|y| (rect.min.x..rect.max.x).map(move |x| (x, y)))
+            .map(|(x, y)| (x, y, material(x, y)))
+            .collect();
+        let body = PixelBody::new(pixels);
+        let position = WorldService::pixel_center_to_world(body.origin.x, body.origin.y);
+        (Transform::from_translation(position.extend(1.0)), body)
+    }
+
+    #[test]
+    fn fallen_trees_settle_into_ground() {
+        let world = world_with_ground(4, Material::DIRT);
+        let (transform, tree) = body(IRect::new(20, 590, 80, 600), |_, y| if y < 594 { Material::LEAF } else { Material::WOOD });
+
+        let placed = rasterise_chunk(&world, &transform, &tree).unwrap();
+        assert_eq!(placed.len(), tree.pixels.len());
+        assert!(placed.iter().all(|(_, _, material, _)| material.props().ground));
+        assert!(placed.iter().any(|(_, _, material, _)| is_outlined(*material)));
+    }
+
+    #[test]
+    fn bodies_that_would_not_be_ground_stay_bodies() {
+        let world = world_with_ground(4, Material::DIRT);
+        let (transform, embers) = body(IRect::new(20, 594, 80, 600), |_, _| Material::EMBER);
+        assert!(rasterise_chunk(&world, &transform, &embers).is_none());
+    }
+
+    /// Drop a wood body onto a settled log as hard as `deceleration`, and return the sizes
+    /// of the bodies afterwards
+    fn hit_settled_log(deceleration: f32) -> Vec<usize> {
+        let log = MaterialRegistry::global().by_name("Log").unwrap();
+        let mut world = world_with_ground(4, Material::DIRT);
+        for y in 595..600 {
+            for x in 20..80 {
+                world.set(x, y, log);
+            }
+        }
+        let mut app = App::new();
+        app.insert_resource(world)
+            .add_event::<ContactForceEvent>()
+            .add_systems(Update, unsettle_hit_pixels);
+
+        let (transform, falling) = body(IRect::new(40, 585, 60, 595), |_, _| Material::WOOD);
+        let force = body_mass(&falling) * deceleration;
+        let body = app.world_mut().spawn((transform, falling)).id();
+        let ground = app.world_mut().spawn(GroundCollider { chunk: IVec2::new(1, 18) }).id();
+        app.world_mut().send_event(ContactForceEvent {
+            collider1: body,
+            collider2: ground,
+            total_force: Vec2::new(0.0, force),
+            total_force_magnitude: force,
+            max_force_direction: Vec2::Y,
+            max_force_magnitude: force,
+        });
+        app.update();
+
+        let mut query = app.world_mut().query::<&PixelBody>();
+        let mut sizes: Vec<usize> = query.iter(app.world()).map(|body| body.pixels.len()).collect();
+        sizes.sort();
+        sizes
+    }
+
+    #[test]
+    fn hard_hit_knocks_a_settled_log_loose() {
+        assert_eq!(hit_settled_log(UNSETTLE_IMPACT * 2.0), vec![200, 300]);
+    }
+
+    #[test]
+    fn resting_on_a_settled_log_leaves_it_settled() {
+        assert_eq!(hit_settled_log(UNSETTLE_IMPACT / 10.0), vec![200]);
+    }
+}
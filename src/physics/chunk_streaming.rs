@@ -0,0 +1,22 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use crate::world::{PixelWorld, WorldService};
+use super::components::PixelBody;
+
+/// Freeze chunks over unloaded parts of the world so they don't fall through the
+/// missing terrain, and let them go again once the ground under them streams back in
+pub fn freeze_unloaded_chunks(
+    mut commands: Commands,
+    world: Res<PixelWorld>,
+    chunks: Query<(Entity, &Transform, Has<RigidBodyDisabled>), With<PixelBody>>,
+) {
+    for (entity, transform, disabled) in chunks.iter() {
+        let (x, y) = WorldService::world_to_pixel(transform.translation.truncate());
+        let unloaded = world.is_unloaded(x, y.clamp(0, world.height - 1));
+        if unloaded && !disabled {
+            commands.entity(entity).insert(RigidBodyDisabled);
+        } else if !unloaded && disabled {
+            commands.entity(entity).remove::<RigidBodyDisabled>();
+        }
+    }
+}
@@ -1,26 +1,90 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
-use super::components::WoodChunk;
+use super::components::PixelBody;
+use crate::world::contours::{simplify_loop, trace_contours};
+use crate::world::Material;
 
-/// Update colliders for wood chunks when their pixels change
+/// How far (in pixels) a simplified collider outline may stray from the traced one
+const OUTLINE_TOLERANCE: f32 = 0.25;
+
+/// Mass of a pixel per point of its material's density
+const MASS_PER_DENSITY: f32 = 0.2;
+
+/// Update colliders and mass for wood chunks when their pixels change
 pub fn update_chunk_colliders(
-    mut chunk_query: Query<(&WoodChunk, &mut Collider), Changed<WoodChunk>>,
+    mut chunk_query: Query<(&PixelBody, &mut Collider, &mut ColliderMassProperties), Changed<PixelBody>>,
 ) {
-    for (chunk, mut collider) in chunk_query.iter_mut() {
+    for (chunk, mut collider, mut mass) in chunk_query.iter_mut() {
         if chunk.pixels.is_empty() {
             continue;
         }
 
-        // Recalculate bounding box
-        let min_x = chunk.pixels.iter().map(|(x, _, _)| *x).min().unwrap();
-        let max_x = chunk.pixels.iter().map(|(x, _, _)| *x).max().unwrap();
-        let min_y = chunk.pixels.iter().map(|(_, y, _)| *y).min().unwrap();
-        let max_y = chunk.pixels.iter().map(|(_, y, _)| *y).max().unwrap();
+        // Update the collider to match the new shape
+        *collider = chunk_collider(chunk);
+        *mass = chunk_mass(chunk);
+    }
+}
+
+/// Mass, centre of mass and rotational inertia summed over the chunk's pixels from their
+/// materials' densities, rather than Rapier's uniform density over the collider
+pub fn chunk_mass(chunk: &PixelBody) -> ColliderMassProperties {
+    let center = chunk.local_center_of_mass();
+    let mut inertia = 0.0;
+    for (x, y, material) in &chunk.pixels {
+        // A unit square's own inertia plus its offset from the centre of mass
+        inertia += pixel_mass(*material) * (1.0 / 6.0 + chunk.local_offset(*x, *y).distance_squared(center));
+    }
+    ColliderMassProperties::MassProperties(MassProperties {
+        local_center_of_mass: center,
+        mass: body_mass(chunk),
+        principal_inertia: inertia,
+    })
+}
+
+/// Mass of a whole body, as Rapier is given it
+pub fn body_mass(chunk: &PixelBody) -> f32 {
+    chunk.pixels.iter().map(|(_, _, material)| pixel_mass(*material)).sum()
+}
+
+/// Mass of one pixel of a body
+pub fn pixel_mass(material: Material) -> f32 {
+    (material.density() as f32 * MASS_PER_DENSITY).max(0.01)
+}
+
+/// Collider following the chunk's silhouette: its pixels are outlined with marching squares,
+/// the outlines simplified, then split into convex pieces Rapier can collide
+pub fn chunk_collider(chunk: &PixelBody) -> Collider {
+    let min_x = chunk.pixels.iter().map(|(x, _, _)| *x).min().unwrap_or(0);
+    let max_x = chunk.pixels.iter().map(|(x, _, _)| *x).max().unwrap_or(0);
+    let min_y = chunk.pixels.iter().map(|(_, y, _)| *y).min().unwrap_or(0);
+    let max_y = chunk.pixels.iter().map(|(_, y, _)| *y).max().unwrap_or(0);
+    let width = max_x - min_x + 1;
+    let height = max_y - min_y + 1;
+
+    let mut filled = vec![false; (width * height) as usize];
+    for (x, y, _) in &chunk.pixels {
+        filled[((y - min_y) * width + (x - min_x)) as usize] = true;
+    }
 
-        let width = (max_x - min_x + 1) as f32;
-        let height = (max_y - min_y + 1) as f32;
+    // Collider space is centred on the chunk's origin pixel, with y up
+    let origin = Vec2::new((chunk.origin.x - min_x) as f32, (chunk.origin.y - min_y) as f32);
+
+    let mut vertices: Vec<Vect> = Vec::new();
+    let mut indices: Vec<[u32; 2]> = Vec::new();
+    for outline in trace_contours(width, height, |x, y| filled[(y * width + x) as usize]) {
+        let outline = simplify_loop(&outline, OUTLINE_TOLERANCE);
+        if outline.len() < 3 {
+            continue;
+        }
+        let first = vertices.len() as u32;
+        let count = outline.len() as u32;
+        vertices.extend(outline.iter().map(|p| Vec2::new(p.x - origin.x, origin.y - p.y)));
+        indices.extend((0..count).map(|i| [first + i, first + (i + 1) % count]));
+    }
 
-        // Update the collider to match new size
-        *collider = Collider::cuboid(width / 2.0, height / 2.0);
+    if indices.is_empty() {
+        // Too small to outline - a lone pixel still needs something to collide with
+        return Collider::cuboid(0.5, 0.5);
     }
+    Collider::convex_decomposition(&vertices, &indices)
 }
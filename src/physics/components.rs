@@ -1,7 +1,127 @@
+use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
-use crate::world::Material;
+use crate::world::{Material, WorldService};
 
+/// Pixels that broke away from the world and move together as one rigid body
 #[derive(Component)]
-pub struct WoodChunk {
+pub struct PixelBody {
+    /// Pixels at the world positions they broke off from
     pub pixels: Vec<(i32, i32, Material)>,
+    /// World pixel whose centre the body's transform sits on. It stays put as pixels are cut away or the
+    /// chunk splits, so the remaining pixels never jump around.
+    pub origin: IVec2,
+}
+
+impl PixelBody {
+    /// A chunk with its origin on the pixel nearest its centre of mass
+    pub fn new(pixels: Vec<(i32, i32, Material)>) -> Self {
+        let center = center_of_mass(&pixels);
+        Self {
+            pixels,
+            origin: center.round().as_ivec2(),
+        }
+    }
+
+    /// Where a pixel sits relative to the body's transform, with y up
+    pub fn local_offset(&self, x: i32, y: i32) -> Vec2 {
+        Vec2::new((x - self.origin.x) as f32, (self.origin.y - y) as f32)
+    }
+
+    /// World pixels the body covers at `transform`, bottom row first, each with the body
+    /// pixel showing there. Each world pixel takes the body pixel under its centre, so
+    /// rotated bodies come out without holes.
+    pub fn footprint(&self, transform: &Transform) -> Vec<(i32, i32, (i32, i32, Material))> {
+        let translation = transform.translation.truncate();
+        let rotation = transform.rotation.to_euler(EulerRot::XYZ).2;
+        let (sin, cos) = rotation.sin_cos();
+        let to_world = |offset: Vec2| translation + Vec2::new(offset.x * cos - offset.y * sin, offset.x * sin + offset.y * cos);
+
+        let sources: HashMap<(i32, i32), Material> = self.pixels.iter().map(|(x, y, m)| ((*x, *y), *m)).collect();
+
+        // World pixels the body could cover
+        let landing: Vec<(i32, i32)> = self
+            .pixels
+            .iter()
+            .map(|(x, y, _)| WorldService::world_to_pixel(to_world(self.local_offset(*x, *y))))
+            .collect();
+        let (Some(min_x), Some(max_x)) = (landing.iter().map(|(x, _)| *x).min(), landing.iter().map(|(x, _)| *x).max()) else {
+            return Vec::new();
+        };
+        let min_y = landing.iter().map(|(_, y)| *y).min().unwrap_or(0);
+        let max_y = landing.iter().map(|(_, y)| *y).max().unwrap_or(0);
+
+        let mut covered = Vec::new();
+        for y in (min_y - 1..=max_y + 1).rev() {
+            for x in min_x - 1..=max_x + 1 {
+                let offset = WorldService::pixel_center_to_world(x, y) - translation;
+                let local = Vec2::new(offset.x * cos + offset.y * sin, offset.y * cos - offset.x * sin);
+                let source = (self.origin.x + local.x.round() as i32, self.origin.y - local.y.round() as i32);
+                if let Some(&material) = sources.get(&source) {
+                    covered.push((x, y, (source.0, source.1, material)));
+                }
+            }
+        }
+        covered
+    }
+
+    /// Centre of mass relative to the body's transform, with y up
+    pub fn local_center_of_mass(&self) -> Vec2 {
+        let center = center_of_mass(&self.pixels);
+        Vec2::new(center.x - self.origin.x as f32, self.origin.y as f32 - center.y)
+    }
+}
+
+/// Centre of mass of pixels in world pixel coordinates, each weighted by its material's
+/// density so a trunk outweighs the leaves on it
+pub fn center_of_mass(pixels: &[(i32, i32, Material)]) -> Vec2 {
+    let mut total = 0.0;
+    let mut sum = Vec2::ZERO;
+    for (x, y, material) in pixels {
+        // Weightless materials still count a little so the centre is always somewhere
+        let weight = (material.density() as f32).max(0.1);
+        total += weight;
+        sum += Vec2::new(*x as f32, *y as f32) * weight;
+    }
+    if total > 0.0 { sum / total } else { Vec2::ZERO }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Body and transform for an L of wood, as a body breaking off the world gets them
+    fn broken_off() -> (PixelBody, Transform) {
+        let pixels: Vec<(i32, i32, Material)> = (100..110)
+            .map(|y| (50, y, Material::WOOD))
+            .chain((51..56).map(|x| (x, 109, Material::WOOD)))
+            .collect();
+        let body = PixelBody::new(pixels);
+        let position = WorldService::pixel_center_to_world(body.origin.x, body.origin.y);
+        (body, Transform::from_translation(position.extend(1.0)))
+    }
+
+    #[test]
+    fn body_covers_the_pixels_it_broke_off_from() {
+        let (body, transform) = broken_off();
+        let mut covered: Vec<(i32, i32)> = body.footprint(&transform).iter().map(|(x, y, _)| (*x, *y)).collect();
+        let mut pixels: Vec<(i32, i32)> = body.pixels.iter().map(|(x, y, _)| (*x, *y)).collect();
+        covered.sort();
+        pixels.sort();
+        assert_eq!(covered, pixels);
+        // Each world pixel shows the body pixel that came from it
+        assert!(body.footprint(&transform).iter().all(|(x, y, (sx, sy, _))| (x, y) == (sx, sy)));
+    }
+
+    #[test]
+    fn quarter_turn_turns_the_body_about_its_origin_pixel() {
+        let (body, transform) = broken_off();
+        let turned = transform.with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2));
+        let footprint = body.footprint(&turned);
+        assert_eq!(footprint.len(), body.pixels.len());
+        for (x, y, (sx, sy, _)) in footprint {
+            // Anticlockwise on screen, with pixel y growing down
+            let (dx, dy) = (sx - body.origin.x, sy - body.origin.y);
+            assert_eq!((x, y), (body.origin.x + dy, body.origin.y - dx));
+        }
+    }
 }
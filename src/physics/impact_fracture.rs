@@ -0,0 +1,198 @@
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use super::chunk_settling::UNSETTLE_IMPACT;
+use super::collider_update::body_mass;
+use super::components::PixelBody;
+use crate::world::contours::distance_to_segment;
+use crate::world::ground_colliders::is_outlined;
+use crate::world::{Material, ParticleSpawnEvent, PixelWorld, WorldService};
+
+/// How far (in pixels) from a body's pixel something has to be to count as touching it.
+/// Colliders are simplified outlines, so they can rest a pixel apart.
+const CONTACT_REACH: i32 = 2;
+
+/// Length of a crack per time its material's `impact_strength` an impact is
+const CRACK_LENGTH: f32 = 8.0;
+const MAX_CRACK_LENGTH: f32 = 64.0;
+
+/// Pixels this close to a crack's line break
+const CRACK_HALF_WIDTH: f32 = 1.0;
+
+/// One impact cracks a body in at most this many places, this far apart, so a trunk
+/// landing flat breaks into logs rather than crumbs
+const MAX_CRACKS: usize = 3;
+const CRACK_SPACING: f32 = 24.0;
+
+/// Ask Rapier for contact force events on bodies, from the force that would knock settled
+/// pixels loose or crack their weakest material
+pub fn watch_impacts(mut commands: Commands, body_query: Query<(Entity, &PixelBody), Changed<PixelBody>>) {
+    for (entity, body) in body_query.iter() {
+        let weakest = body
+            .pixels
+            .iter()
+            .filter_map(|(_, _, material)| material.props().impact_strength)
+            .fold(UNSETTLE_IMPACT, f32::min);
+        commands.entity(entity).insert((
+            ActiveEvents::CONTACT_FORCE_EVENTS,
+            ContactForceEventThreshold(weakest * body_mass(body)),
+        ));
+    }
+}
+
+/// Crack bodies that hit something harder than the material where they touch it can take.
+/// Cracks run from the contact into the body along the impact, longer the harder it hit,
+/// and `detect_split_chunks` breaks the body apart along them. A palm felled off a cliff
+/// lands in several logs.
+pub fn fracture_on_impact(
+    mut contact_events: EventReader<ContactForceEvent>,
+    mut particle_events: EventWriter<ParticleSpawnEvent>,
+    world: Res<PixelWorld>,
+    mut body_query: Query<(&Transform, &mut PixelBody)>,
+) {
+    // Only the hardest hit on each body this frame counts
+    let mut impacts: HashMap<Entity, (f32, Vec2)> = HashMap::new();
+    for event in contact_events.read() {
+        for entity in [event.collider1, event.collider2] {
+            let hit = impacts.entry(entity).or_insert((0.0, Vec2::ZERO));
+            if event.total_force_magnitude > hit.0 {
+                *hit = (event.total_force_magnitude, event.max_force_direction);
+            }
+        }
+    }
+
+    for (entity, (force, direction)) in impacts {
+        let Ok((transform, mut body)) = body_query.get_mut(entity) else {
+            continue;
+        };
+        let deceleration = force / body_mass(&body);
+        let footprint = body.footprint(transform);
+        let cracks = crack_starts(&world, &footprint, deceleration);
+        if cracks.is_empty() {
+            continue;
+        }
+
+        let center = transform.transform_point(body.local_center_of_mass().extend(0.0)).truncate();
+        let mut broken = HashSet::new();
+        for (start, length) in cracks {
+            // Into the body, whichever way round Rapier reports the force
+            let inwards = center - start;
+            let along = if direction.dot(inwards) < 0.0 { -direction } else { direction };
+            let along = if along == Vec2::ZERO { inwards.normalize_or_zero() } else { along };
+            let end = start + along * length;
+
+            let mut first = None;
+            for &(x, y, (source_x, source_y, material)) in &footprint {
+                let position = WorldService::pixel_center_to_world(x, y);
+                if distance_to_segment(position, start, end) <= CRACK_HALF_WIDTH {
+                    broken.insert((source_x, source_y));
+                    first.get_or_insert((position, material));
+                }
+            }
+            if let Some((position, material)) = first {
+                particle_events.write(ParticleSpawnEvent { position, material });
+            }
+        }
+
+        if !broken.is_empty() {
+            body.pixels.retain(|(x, y, _)| !broken.contains(&(*x, *y)));
+            info!("Impact of {:.0} cracked a body, breaking {} pixels", deceleration, broken.len());
+        }
+    }
+}
+
+/// Where cracks start on a body that hit something with `deceleration`, and how long they
+/// are: at the pixels touching whatever it hit whose material can't take the impact,
+/// nearest the middle of the contact first
+fn crack_starts(world: &PixelWorld, footprint: &[(i32, i32, (i32, i32, Material))], deceleration: f32) -> Vec<(Vec2, f32)> {
+    let own: HashSet<(i32, i32)> = footprint.iter().map(|(x, y, _)| (*x, *y)).collect();
+    let touching = |x: i32, y: i32| {
+        (-CONTACT_REACH..=CONTACT_REACH).any(|dy| {
+            (-CONTACT_REACH..=CONTACT_REACH).any(|dx| {
+                let (nx, ny) = (x + dx, y + dy);
+                !own.contains(&(nx, ny)) && (is_outlined(world.get(nx, ny)) || world.is_covered_by_body(nx, ny))
+            })
+        })
+    };
+
+    let contacts: Vec<(Vec2, f32)> = footprint
+        .iter()
+        .filter_map(|&(x, y, (_, _, material))| {
+            let strength = material.props().impact_strength?;
+            (deceleration > strength && touching(x, y)).then(|| {
+                let length = (CRACK_LENGTH * deceleration / strength).min(MAX_CRACK_LENGTH);
+                (WorldService::pixel_center_to_world(x, y), length)
+            })
+        })
+        .collect();
+    if contacts.is_empty() {
+        return Vec::new();
+    }
+
+    let middle = contacts.iter().map(|(p, _)| *p).sum::<Vec2>() / contacts.len() as f32;
+    let mut sorted = contacts;
+    sorted.sort_by(|a, b| a.0.distance_squared(middle).total_cmp(&b.0.distance_squared(middle)));
+    let mut starts: Vec<(Vec2, f32)> = Vec::new();
+    for (position, length) in sorted {
+        if starts.len() == MAX_CRACKS {
+            break;
+        }
+        if starts.iter().all(|(start, _)| start.distance(position) >= CRACK_SPACING) {
+            starts.push((position, length));
+        }
+    }
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::pixel_world::test_support::world_with_ground;
+
+    /// Hit a 100x6 wood log lying on stone as if it landed at `speed` pixels per second,
+    /// and return how many of each of its columns' pixels are left
+    fn land_log(speed: f32) -> Vec<usize> {
+        let mut app = App::new();
+        app.insert_resource(world_with_ground(4, Material::STONE))
+            .add_event::<ContactForceEvent>()
+            .add_event::<ParticleSpawnEvent>()
+            .add_systems(Update, (watch_impacts, fracture_on_impact));
+
+        let log = PixelBody::new((10..110).flat_map(|x| (594..600).map(move |y| (x, y, Material::WOOD))).collect());
+        let position = WorldService::pixel_center_to_world(log.origin.x, log.origin.y);
+        let force = body_mass(&log) * speed * 60.0;
+        let body = app.world_mut().spawn((Transform::from_translation(position.extend(1.0)), log)).id();
+        let ground = app.world_mut().spawn_empty().id();
+        app.update();
+        assert!(app.world().get::<ContactForceEventThreshold>(body).is_some());
+
+        app.world_mut().send_event(ContactForceEvent {
+            collider1: ground,
+            collider2: body,
+            total_force: Vec2::new(0.0, force),
+            total_force_magnitude: force,
+            max_force_direction: Vec2::Y,
+            max_force_magnitude: force,
+        });
+        app.update();
+        let pixels = &app.world().get::<PixelBody>(body).unwrap().pixels;
+        (10..110).map(|x| pixels.iter().filter(|(px, _, _)| *px == x).count()).collect()
+    }
+
+    #[test]
+    fn gentle_landing_leaves_the_log_whole() {
+        assert!(land_log(50.0).iter().all(|left| *left == 6));
+    }
+
+    #[test]
+    fn hard_landing_cracks_the_log_right_through_in_places() {
+        let columns = land_log(150.0);
+        // Columns the cracks went right through, so the log splits there
+        let cut: Vec<usize> = (0..columns.len()).filter(|x| columns[*x] == 0).collect();
+        let cracks = 1 + cut.windows(2).filter(|pair| pair[1] - pair[0] > 1).count();
+        assert!(!cut.is_empty());
+        assert_eq!(cracks, MAX_CRACKS);
+        // Everything else is untouched
+        assert!(columns.iter().all(|left| *left == 0 || *left == 6));
+    }
+}
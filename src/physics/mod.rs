@@ -3,6 +3,13 @@ pub mod chunk_detection;
 pub mod chunk_rendering;
 pub mod chunk_splitting;
 pub mod collider_update;
+pub mod chunk_burning;
+pub mod chunk_streaming;
+pub mod chunk_settling;
+pub mod structural_stress;
+pub mod pixel_displacement;
+pub mod buoyancy;
+pub mod impact_fracture;
 
 use bevy::prelude::*;
 
@@ -10,11 +17,28 @@ pub struct PhysicsPlugin;
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<structural_stress::StressTimer>()
+            .init_resource::<pixel_displacement::RestingOnPowder>();
         app.add_systems(Update, (
-            chunk_detection::detect_floating_chunks,
-            chunk_rendering::render_wood_chunks,
+            // Pixels that break under load let go of what they held in the same frame
+            (structural_stress::break_overloaded_pixels, chunk_detection::detect_floating_chunks).chain(),
+            chunk_rendering::bake_pixel_bodies,
             chunk_splitting::detect_split_chunks,
+            impact_fracture::watch_impacts,
+            // Bodies cracked by a hit break apart in the same frame. Body cover has to be this
+            // frame's, or a moving body would hit its own trail.
+            impact_fracture::fracture_on_impact
+                .after(pixel_displacement::displace_loose_pixels)
+                .before(chunk_splitting::detect_split_chunks),
             collider_update::update_chunk_colliders,
+            chunk_burning::burn_pixel_bodies,
+            chunk_streaming::freeze_unloaded_chunks,
+            chunk_settling::settle_resting_chunks,
+            chunk_settling::unsettle_hit_pixels,
+            // Bodies clear loose pixels out of the way before the simulation moves them
+            pixel_displacement::displace_loose_pixels.before(crate::world::simulation::update_pixels),
+            // Powder that held a body up this frame goes into its forces
+            buoyancy::float_in_liquids.after(pixel_displacement::displace_loose_pixels),
         ));
     }
 }
@@ -0,0 +1,191 @@
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use std::collections::VecDeque;
+use super::collider_update::{body_mass, pixel_mass};
+use super::components::PixelBody;
+use crate::world::materials::MaterialState;
+use crate::world::{Material, ParticleSpawnEvent, PixelWorld, WorldService};
+
+/// How far (in steps up or sideways) a pushed pixel may go to find room - far enough to
+/// get over a log, through the body and any loose pixels in the way
+const DISPLACE_REACH: i32 = 16;
+
+/// Faster than this (pixels per second) a body throws up what it pushes aside
+const SPLASH_SPEED: f32 = 60.0;
+
+/// Slower than this (pixels per second) a body can't push powder aside and rests on it
+const PACK_SPEED: f32 = 20.0;
+
+/// Bodies held up by powder that didn't give way this frame. Sand has no ground collider,
+/// so `float_in_liquids` pushes these up against their weight.
+#[derive(Resource, Default)]
+pub struct RestingOnPowder(pub HashSet<Entity>);
+
+/// Push powders and liquids out of the way of rigid bodies, and record which pixels the
+/// bodies cover so the simulation lets loose pixels rest on them.
+///
+/// Each pushed pixel moves to the nearest empty cell above or beside it and slows the body
+/// as if it had hit it. A liquid with nowhere to go squirts out as particles. Powder only
+/// gives way to a body moving fast enough and otherwise holds it up, so a log dropped on a
+/// beach digs in until the sand has slowed it down, then rests there.
+pub fn displace_loose_pixels(
+    mut world: ResMut<PixelWorld>,
+    mut resting: ResMut<RestingOnPowder>,
+    mut particle_events: EventWriter<ParticleSpawnEvent>,
+    mut body_query: Query<(Entity, &Transform, &mut Velocity, &PixelBody)>,
+) {
+    let footprints: Vec<Vec<(i32, i32)>> = body_query
+        .iter()
+        .map(|(_, transform, _, body)| body.footprint(transform).into_iter().map(|(x, y, _)| (x, y)).collect())
+        .collect();
+    let covered: HashSet<(i32, i32)> = footprints.iter().flatten().copied().collect();
+
+    resting.0.clear();
+    for ((entity, _, mut velocity, body), footprint) in body_query.iter_mut().zip(&footprints) {
+        let mut pushed_mass = 0.0;
+        let mut packed = false;
+        for &(x, y) in footprint {
+            let material = world.get(x, y);
+            if !material.is_powder() && !material.is_liquid() {
+                continue;
+            }
+
+            if material.is_powder() && velocity.linvel.length() < PACK_SPEED {
+                packed = true;
+                continue;
+            }
+            match find_room(&world, &covered, x, y) {
+                Some((tx, ty)) => {
+                    let (lifetime, shade) = (world.lifetime(x, y), world.shade(x, y));
+                    world.set(x, y, Material::AIR);
+                    world.set_with_lifetime(tx, ty, material, lifetime);
+                    world.set_shade(tx, ty, shade);
+                    if velocity.linvel.length() > SPLASH_SPEED {
+                        particle_events.write(ParticleSpawnEvent {
+                            position: WorldService::pixel_to_world(tx, ty),
+                            material,
+                        });
+                    }
+                    pushed_mass += pixel_mass(material);
+                }
+                None if material.is_liquid() => {
+                    world.set(x, y, Material::AIR);
+                    particle_events.write(ParticleSpawnEvent {
+                        position: WorldService::pixel_to_world(x, y),
+                        material,
+                    });
+                    pushed_mass += pixel_mass(material);
+                }
+                None => packed = true,
+            }
+        }
+
+        // Pushing pixels aside is an inelastic collision with each of them
+        if pushed_mass > 0.0 {
+            let mass = body_mass(body);
+            velocity.linvel *= mass / (mass + pushed_mass);
+        }
+
+        // Powder that didn't give way holds the body up
+        if packed {
+            velocity.linvel.y = velocity.linvel.y.max(0.0);
+            resting.0.insert(entity);
+        }
+    }
+
+    world.set_body_cover(covered);
+}
+
+/// Nearest empty cell a loose pixel at (x, y) can be pushed to, moving up or sideways
+/// through bodies, other loose pixels and gas. Gas is passed through rather than written
+/// over, so smoke above a body isn't deleted.
+fn find_room(world: &PixelWorld, covered: &HashSet<(i32, i32)>, x: i32, y: i32) -> Option<(i32, i32)> {
+    let mut visited = HashSet::from([(x, y)]);
+    let mut queue = VecDeque::from([(x, y, 0)]);
+    while let Some((cx, cy, steps)) = queue.pop_front() {
+        for (dx, dy) in [(0, -1), (-1, 0), (1, 0)] {
+            let (nx, ny) = (cx + dx, cy + dy);
+            if !world.in_bounds(nx, ny) || !visited.insert((nx, ny)) {
+                continue;
+            }
+            let material = world.get(nx, ny);
+            let in_body = covered.contains(&(nx, ny));
+            if material == Material::AIR && !in_body {
+                return Some((nx, ny));
+            }
+            let open = matches!(material.props().state, MaterialState::Empty | MaterialState::Gas);
+            if (open || material.is_powder() || material.is_liquid()) && steps + 1 < DISPLACE_REACH {
+                queue.push_back((nx, ny, steps + 1));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::buoyancy::float_in_liquids;
+    use crate::world::pixel_world::test_support::{fill, world_with_ground};
+
+    fn displacement_app(world: PixelWorld) -> App {
+        let mut app = App::new();
+        app.insert_resource(world)
+            .init_resource::<RestingOnPowder>()
+            .add_event::<ParticleSpawnEvent>()
+            .add_systems(Update, (displace_loose_pixels, float_in_liquids).chain());
+        app.world_mut().spawn(RapierConfiguration::new(1.0));
+        app
+    }
+
+    /// A 10x3 wood log with its top left pixel at (x, y), moving at `linvel`
+    fn spawn_log(app: &mut App, x: i32, y: i32, linvel: Vec2) -> Entity {
+        let body = PixelBody::new((x..x + 10).flat_map(|x| (y..y + 3).map(move |y| (x, y, Material::WOOD))).collect());
+        let position = WorldService::pixel_center_to_world(body.origin.x, body.origin.y);
+        app.world_mut()
+            .spawn((Transform::from_translation(position.extend(1.0)), Velocity { linvel, angvel: 0.0 }, body))
+            .id()
+    }
+
+    fn count(world: &PixelWorld, rect: IRect, material: Material) -> usize {
+        world.materials_in(rect).iter().filter(|m| **m == material).count()
+    }
+
+    #[test]
+    fn sand_holds_up_a_resting_body_against_its_weight() {
+        let mut world = world_with_ground(3, Material::DIRT);
+        fill(&mut world, IRect::new(0, 590, 96, 600), Material::SAND);
+        let mut app = displacement_app(world);
+        let log = spawn_log(&mut app, 40, 588, Vec2::ZERO);
+
+        // Forces start the frame after a body appears
+        app.update();
+        app.update();
+
+        assert!(app.world().resource::<RestingOnPowder>().0.contains(&log));
+        let body = app.world().get::<PixelBody>(log).unwrap();
+        let weight = 9.81 * body_mass(body);
+        let force = app.world().get::<ExternalForce>(log).unwrap().force;
+        assert!((force.y - weight).abs() < 1e-3 * weight, "{} against a weight of {}", force.y, weight);
+    }
+
+    #[test]
+    fn sand_pushed_up_through_smoke_leaves_the_smoke_alone() {
+        let mut world = world_with_ground(3, Material::DIRT);
+        let area = IRect::new(0, 570, 96, 600);
+        fill(&mut world, IRect::new(0, 590, 96, 600), Material::SAND);
+        fill(&mut world, IRect::new(0, 582, 96, 590), Material::SMOKE);
+        let (sand, smoke) = (count(&world, area, Material::SAND), count(&world, area, Material::SMOKE));
+        let mut app = displacement_app(world);
+        spawn_log(&mut app, 40, 589, Vec2::new(0.0, -100.0));
+
+        app.update();
+
+        let world = app.world().resource::<PixelWorld>();
+        assert_eq!(count(world, area, Material::SMOKE), smoke);
+        assert_eq!(count(world, area, Material::SAND), sand);
+        // The sand the log dug into came out above the smoke
+        assert!(count(world, IRect::new(0, 570, 96, 582), Material::SAND) > 0);
+    }
+}
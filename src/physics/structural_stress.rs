@@ -0,0 +1,369 @@
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+use crate::world::materials::{MaterialState, Unsupported};
+use crate::world::{Material, ParticleSpawnEvent, PixelWorld, WorldService};
+
+/// Biggest structure loads are worked out for. Bigger ones, like a pile of settled logs
+/// the size of a hill, are left as they are, the way support searches give up.
+const MAX_STRUCTURE_PIXELS: usize = 50_000;
+
+/// What it costs to hand weight down, diagonally down, to the side and up. Weight takes
+/// the cheapest path to support, so things rest on what's below them rather than hang
+/// off what's beside them, and staircases like roots and leaning trunks rest diagonally.
+const DOWN_COST: u32 = 2;
+const DIAGONAL_COST: u32 = 3;
+const SIDE_COST: u32 = 4;
+const UP_COST: u32 = 8;
+
+/// Pixels at least this close to the worst overload break along with it, so a beam
+/// cracks cleanly across instead of crumbling from one end
+const BREAK_SPREAD: f32 = 0.9;
+
+#[derive(Resource)]
+pub struct StressTimer {
+    pub timer: Timer,
+}
+
+impl Default for StressTimer {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(0.1, TimerMode::Repeating),
+        }
+    }
+}
+
+/// Work out how much weight each solid pixel carries near where solids were added or
+/// removed, and break the ones carrying more than their material's strength. Whatever
+/// they held up is then picked up by `detect_floating_chunks`, so a half-cut trunk
+/// snaps under the tree's weight and a long ledge cracks off at its root.
+pub fn break_overloaded_pixels(
+    mut world: ResMut<PixelWorld>,
+    mut particle_events: EventWriter<ParticleSpawnEvent>,
+    mut timer: ResMut<StressTimer>,
+    time: Res<Time>,
+) {
+    if !timer.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let changed = world.take_load_changed();
+    if changed.is_empty() {
+        return;
+    }
+
+    // Only the structures touching a change can carry anything different, and what they
+    // carry depends on nothing but their own pixels and what they rest on
+    let mut seen = HashSet::new();
+    let mut broken = Vec::new();
+    for (x, y) in changed {
+        for (dx, dy) in NEIGHBOURS.into_iter().chain([(0, 0)]) {
+            let start = (x + dx, y + dy);
+            if seen.contains(&start) || !is_structural(world.get(start.0, start.1)) {
+                continue;
+            }
+            if let Some(structure) = structure_at(&world, start, &mut seen) {
+                broken.extend(overloaded_pixels(&world, &structure));
+            }
+        }
+    }
+
+    let mut count = 0;
+    for (x, y) in broken {
+        let material = world.get(x, y);
+        world.set(x, y, Material::AIR);
+        particle_events.write(ParticleSpawnEvent {
+            position: WorldService::pixel_to_world(x, y),
+            material,
+        });
+        count += 1;
+    }
+    if count > 0 {
+        info!("{} overloaded pixels broke", count);
+    }
+}
+
+/// Pixels around a pixel, diagonals included, since weight is handed on diagonally too
+const NEIGHBOURS: [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// Materials that carry weight and can break under it. Everything else that carries
+/// weight - the ground, powders - holds up whatever touches it, so caves the world was
+/// generated with never cave in.
+fn is_structural(material: Material) -> bool {
+    let props = material.props();
+    props.state == MaterialState::Solid
+        && props.unsupported != Unsupported::Stays
+        && (props.compressive_strength.is_some() || props.tensile_strength.is_some())
+}
+
+/// Structural pixels connected to `start`, adding them to `seen`, or None if there are
+/// more than `MAX_STRUCTURE_PIXELS` of them
+fn structure_at(world: &PixelWorld, start: (i32, i32), seen: &mut HashSet<(i32, i32)>) -> Option<Vec<(i32, i32)>> {
+    seen.insert(start);
+    let mut structure = vec![start];
+    let mut next = 0;
+    while let Some(&(x, y)) = structure.get(next) {
+        next += 1;
+        if structure.len() > MAX_STRUCTURE_PIXELS {
+            return None;
+        }
+        for (dx, dy) in NEIGHBOURS {
+            let neighbour = (x + dx, y + dy);
+            if !seen.contains(&neighbour) && is_structural(world.get(neighbour.0, neighbour.1)) {
+                seen.insert(neighbour);
+                structure.push(neighbour);
+            }
+        }
+    }
+    Some(structure)
+}
+
+/// Pixels of a structure carrying more than they can take, worst first
+fn overloaded_pixels(world: &PixelWorld, structure: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    // Loads are worked out over the structure and what it rests on around it
+    let (min, max) = structure.iter().fold((IVec2::MAX, IVec2::MIN), |(min, max), &(x, y)| {
+        (min.min(IVec2::new(x, y)), max.max(IVec2::new(x, y)))
+    });
+    let area = IRect::from_corners(min - IVec2::ONE, max + IVec2::splat(2));
+    let mut strain: Vec<(f32, i32, i32)> = carried_loads(world, area, structure)
+        .into_iter()
+        .filter_map(|(load, carry, x, y)| {
+            let props = world.get(x, y).props();
+            let strength = match carry {
+                Carry::Down => props.compressive_strength,
+                _ => props.tensile_strength,
+            };
+            strength.map(|strength| (load / strength, x, y))
+        })
+        .collect();
+
+    let worst = strain.iter().map(|(s, _, _)| *s).fold(0.0, f32::max);
+    if worst <= 1.0 {
+        return Vec::new();
+    }
+    strain.retain(|(s, _, _)| *s > 1.0 && *s >= worst * BREAK_SPREAD);
+    strain.into_iter().map(|(_, x, y)| (x, y)).collect()
+}
+
+/// Which way a pixel passes on the weight it carries
+#[derive(Clone, Copy, PartialEq)]
+enum Carry {
+    /// Resting on what's below or diagonally below, or on whatever holds it up
+    Down,
+    /// Hanging off its neighbour on that side
+    Side(i32),
+    /// Hanging from what's above
+    Up,
+}
+
+/// Weight each pixel of `structure` carries, and which way, in world pixel coordinates.
+/// `area` has to hold the structure and the pixels around it.
+///
+/// Each pixel's weight is handed along the cheapest path to something holding it up -
+/// straight down where it can, then diagonally down, then to the sides, then up.
+/// Powders resting on structures aren't counted.
+fn carried_loads(world: &PixelWorld, area: IRect, structure: &[(i32, i32)]) -> Vec<(f32, Carry, i32, i32)> {
+    let materials = world.materials_in(area);
+    let (width, height) = (area.width(), area.height());
+    let index = |x: i32, y: i32| (y * width + x) as usize;
+    let mut in_structure = vec![false; materials.len()];
+    for &(x, y) in structure {
+        in_structure[index(x - area.min.x, y - area.min.y)] = true;
+    }
+    let ground = |material: Material| material.is_solid() && !is_structural(material);
+
+    // Structures touching the ground or the bottom of the world are held up there, and so
+    // are ones next to unloaded chunks, since we can't see what's past them
+    let mut distance = vec![u32::MAX; materials.len()];
+    let mut to_check = vec![Vec::new()];
+    for y in 0..height {
+        for x in 0..width {
+            let i = index(x, y);
+            if !in_structure[i] {
+                continue;
+            }
+            let (world_x, world_y) = (area.min.x + x, area.min.y + y);
+            let supported = world_y == world.height - 1
+                || [(-1, 0), (1, 0), (0, -1), (0, 1)].iter().any(|(dx, dy)| {
+                    let (nx, ny) = (x + dx, y + dy);
+                    let inside = nx >= 0 && ny >= 0 && nx < width && ny < height;
+                    (inside && ground(materials[index(nx, ny)])) || world.is_unloaded(world_x + dx, world_y + dy)
+                });
+            if supported {
+                distance[i] = 0;
+                to_check[0].push((x, y));
+            }
+        }
+    }
+
+    // Cost of the cheapest path to support through solids, worked out cheapest first with
+    // a queue per cost. `order` ends up sorted by cost. Each step leads to a neighbour that
+    // would hand its weight here: the one above hands it down, the one below hands it up.
+    let steps = [
+        (0, -1, DOWN_COST),
+        (-1, -1, DIAGONAL_COST),
+        (1, -1, DIAGONAL_COST),
+        (-1, 0, SIDE_COST),
+        (1, 0, SIDE_COST),
+        (0, 1, UP_COST),
+    ];
+    let mut order = Vec::new();
+    let mut cost = 0;
+    while cost < to_check.len() {
+        while let Some((x, y)) = to_check[cost].pop() {
+            if distance[index(x, y)] < cost as u32 {
+                continue;
+            }
+            order.push((x, y));
+            for (dx, dy, step) in steps {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                    continue;
+                }
+                let i = index(nx, ny);
+                let next = cost as u32 + step;
+                if next < distance[i] && in_structure[i] {
+                    distance[i] = next;
+                    if to_check.len() <= next as usize {
+                        to_check.resize_with(next as usize + 1, Vec::new);
+                    }
+                    to_check[next as usize].push((nx, ny));
+                }
+            }
+        }
+        cost += 1;
+    }
+
+    // Hand weight towards support, furthest pixels first, so each pixel has everything
+    // it carries before passing it on
+    let mut load: Vec<f32> = materials.iter().map(|m| m.props().density as f32).collect();
+    let mut carry = vec![None; materials.len()];
+    for &(x, y) in order.iter().rev() {
+        let i = index(x, y);
+        let through = |dx: i32, dy: i32, step: u32| {
+            let (nx, ny) = (x + dx, y + dy);
+            nx >= 0 && ny >= 0 && nx < width && ny < height && distance[index(nx, ny)].saturating_add(step) == distance[i]
+        };
+
+        let diagonals: Vec<i32> = [-1, 1].into_iter().filter(|dx| through(*dx, 1, DIAGONAL_COST)).collect();
+        let sides: Vec<i32> = [-1, 1].into_iter().filter(|dx| through(*dx, 0, SIDE_COST)).collect();
+        let (kind, targets): (Carry, Vec<(i32, i32)>) = if distance[i] == 0 {
+            (Carry::Down, Vec::new())
+        } else if through(0, 1, DOWN_COST) {
+            (Carry::Down, vec![(x, y + 1)])
+        } else if !diagonals.is_empty() {
+            (Carry::Down, diagonals.iter().map(|dx| (x + dx, y + 1)).collect())
+        } else if !sides.is_empty() {
+            (Carry::Side(sides[0]), sides.iter().map(|dx| (x + dx, y)).collect())
+        } else {
+            (Carry::Up, vec![(x, y - 1)])
+        };
+        carry[i] = Some(kind);
+        for (tx, ty) in &targets {
+            let j = index(*tx, *ty);
+            load[j] += load[i] / targets.len() as f32;
+        }
+    }
+
+    // Paths bunch up, so each cross-section - a run of pixels along a row carrying down
+    // or up, or down a column carrying to the side - shares the weight through it evenly,
+    // the way a whole trunk holds up its crown
+    let mut loads = Vec::with_capacity(order.len());
+    let mut section = |run: &[usize]| {
+        if run.is_empty() {
+            return;
+        }
+        let average = run.iter().map(|i| load[*i]).sum::<f32>() / run.len() as f32;
+        for &i in run {
+            let (x, y) = (i as i32 % width, i as i32 / width);
+            loads.push((average, carry[i].unwrap(), area.min.x + x, area.min.y + y));
+        }
+    };
+    let mut run = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let i = index(x, y);
+            let same = run.last().is_some_and(|last: &usize| carry[*last] == carry[i]);
+            if !same {
+                section(&run);
+                run.clear();
+            }
+            if matches!(carry[i], Some(Carry::Down | Carry::Up)) {
+                run.push(i);
+            }
+        }
+        section(&run);
+        run.clear();
+    }
+    for x in 0..width {
+        for y in 0..height {
+            let i = index(x, y);
+            let same = run.last().is_some_and(|last: &usize| carry[*last] == carry[i]);
+            if !same {
+                section(&run);
+                run.clear();
+            }
+            if matches!(carry[i], Some(Carry::Side(_))) {
+                run.push(i);
+            }
+        }
+        section(&run);
+        run.clear();
+    }
+    loads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::chunk_detection::detect_floating_chunks;
+    use crate::physics::components::PixelBody;
+    use crate::world::pixel_world::test_support::{fill, world_with_ground};
+    use std::time::Duration;
+
+    /// An 8 pixel wide trunk standing on dirt under a small canopy, with `cut` pixels of
+    /// it cut away from the left a little above the ground
+    fn cut_tree(cut: i32) -> App {
+        let mut world = world_with_ground(6, Material::DIRT);
+        fill(&mut world, IRect::new(80, 460, 88, 600), Material::WOOD);
+        fill(&mut world, IRect::new(69, 440, 99, 460), Material::LEAF);
+
+        let mut app = App::new();
+        app.insert_resource(world)
+            .init_resource::<StressTimer>()
+            .init_resource::<Time>()
+            .add_event::<ParticleSpawnEvent>()
+            .add_systems(Update, (break_overloaded_pixels, detect_floating_chunks).chain());
+        let mut world = app.world_mut().resource_mut::<PixelWorld>();
+        world.take_support_lost();
+        world.take_load_changed();
+        fill(&mut world, IRect::new(80, 590, 80 + cut, 591), Material::AIR);
+
+        for _ in 0..5 {
+            app.world_mut().resource_mut::<Time>().advance_by(Duration::from_millis(100));
+            app.update();
+        }
+        app
+    }
+
+    fn body_sizes(app: &mut App) -> Vec<usize> {
+        let mut query = app.world_mut().query::<&PixelBody>();
+        query.iter(app.world()).map(|body| body.pixels.len()).collect()
+    }
+
+    #[test]
+    fn notched_trunk_holds_the_tree_up() {
+        let mut app = cut_tree(1);
+        assert!(body_sizes(&mut app).is_empty());
+        assert_eq!(app.world().resource::<PixelWorld>().get(84, 590), Material::WOOD);
+    }
+
+    #[test]
+    fn half_cut_trunk_snaps_under_the_tree() {
+        let mut app = cut_tree(4);
+        let bodies = body_sizes(&mut app);
+        assert_eq!(bodies.len(), 1);
+        // Everything above the cut falls together
+        let above_cut = 8 * 130 + 30 * 20;
+        assert!(bodies[0] > above_cut * 9 / 10, "body of {} pixels", bodies[0]);
+        assert_eq!(app.world().resource::<PixelWorld>().get(84, 450), Material::AIR);
+    }
+}
@@ -0,0 +1,100 @@
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use crate::constants::WORLD_PIXEL_HEIGHT;
+use crate::world::WorldService;
+use super::components::Player;
+
+/// How quickly the camera catches up with the player - higher is snappier
+const FOLLOW_SPEED: f32 = 6.0;
+
+/// Zoom limits as orthographic scale: below 1 zooms in, above 1 zooms out.
+/// Zooming out further would show past the chunks kept loaded around the camera.
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 1.5;
+
+/// Scale change per mouse wheel notch
+const ZOOM_STEP: f32 = 0.1;
+
+/// Camera that smoothly follows the player and zooms with the mouse wheel
+#[derive(Component)]
+pub struct FollowCamera {
+    /// Scale the camera is easing towards
+    pub target_zoom: f32,
+}
+
+impl Default for FollowCamera {
+    fn default() -> Self {
+        Self { target_zoom: 1.0 }
+    }
+}
+
+/// Spawn the camera already centred on the player so it doesn't pan in from the origin
+pub fn setup_camera(mut commands: Commands, player: Res<Player>) {
+    let position = WorldService::pixel_pos_to_world(Vec2::new(player.x, player.y));
+    commands.spawn((
+        Camera2d,
+        Transform::from_xyz(position.x, position.y, 0.0),
+        FollowCamera::default(),
+    ));
+}
+
+pub fn zoom_camera(
+    mut wheel_events: EventReader<MouseWheel>,
+    mut camera: Query<(&mut FollowCamera, &mut Projection)>,
+    time: Res<Time>,
+) {
+    let Ok((mut follow, mut projection)) = camera.single_mut() else {
+        return;
+    };
+    let Projection::Orthographic(ortho) = projection.as_mut() else {
+        return;
+    };
+
+    for event in wheel_events.read() {
+        let notches = match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 100.0,
+        };
+        // Scrolling up zooms in
+        follow.target_zoom = (follow.target_zoom * (1.0 - notches * ZOOM_STEP)).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    let blend = 1.0 - (-FOLLOW_SPEED * time.delta_secs()).exp();
+    ortho.scale += (follow.target_zoom - ortho.scale) * blend;
+}
+
+/// Ease the camera towards the player, keeping the view inside the world's rows.
+/// The camera position also decides which chunks are streamed in.
+pub fn follow_player(
+    player: Res<Player>,
+    windows: Query<&Window>,
+    mut camera: Query<(&mut Transform, &Projection), With<FollowCamera>>,
+    time: Res<Time>,
+) {
+    let Ok((mut transform, projection)) = camera.single_mut() else {
+        return;
+    };
+    let scale = match projection {
+        Projection::Orthographic(ortho) => ortho.scale,
+        _ => 1.0,
+    };
+
+    let target = WorldService::pixel_pos_to_world(Vec2::new(player.x, player.y));
+    let blend = 1.0 - (-FOLLOW_SPEED * time.delta_secs()).exp();
+    let mut position = transform.translation.truncate().lerp(target, blend);
+
+    // The world is unbounded sideways, so only the top and bottom need clamping
+    if let Ok(window) = windows.single() {
+        let half_height = window.height() * scale / 2.0;
+        let top = WorldService::pixel_to_world(0, 0).y;
+        let bottom = WorldService::pixel_to_world(0, WORLD_PIXEL_HEIGHT).y;
+        position.y = if top - bottom < half_height * 2.0 {
+            (top + bottom) / 2.0
+        } else {
+            position.y.clamp(bottom + half_height, top - half_height)
+        };
+    }
+
+    transform.translation.x = position.x;
+    transform.translation.y = position.y;
+}
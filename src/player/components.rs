@@ -1,6 +1,14 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::world::PixelWorld;
 
-#[derive(Resource)]
+pub const MAX_HEALTH: f32 = 100.0;
+
+fn full_health() -> f32 {
+    MAX_HEALTH
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone)]
 pub struct Player {
     pub x: f32,  // Use float for smooth sub-pixel movement
     pub y: f32,
@@ -8,6 +16,8 @@ pub struct Player {
     pub vy: f32,
     pub width: i32,
     pub height: i32,
+    #[serde(default = "full_health")]
+    pub health: f32,
 }
 
 impl Default for Player {
@@ -19,10 +29,24 @@ impl Default for Player {
             vy: 0.0,
             width: 8,
             height: 16,
+            health: MAX_HEALTH,
+        }
+    }
+}
+
+impl Player {
+    /// Stand on the ground surface at the player's current x, whatever height it is
+    pub fn move_to_surface(&mut self, world: &PixelWorld) {
+        let x = self.x as i32;
+        if let Some(surface_y) = (0..world.height).find(|&y| world.get(x, y).props().ground) {
+            self.y = (surface_y - self.height / 2 - 1) as f32;
         }
     }
 }
 
-pub fn spawn_player(mut commands: Commands) {
-    commands.insert_resource(Player::default());
+/// Spawn the player standing on the surface, whatever height the terrain generated at
+pub fn spawn_player(mut commands: Commands, world: Res<PixelWorld>) {
+    let mut player = Player::default();
+    player.move_to_surface(&world);
+    commands.insert_resource(player);
 }
@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use crate::tools::components::{CurrentTool, Tool};
+use crate::world::lighting::{light_level, DayNightClock};
+use crate::world::PixelWorld;
+use super::components::{Player, MAX_HEALTH};
+
+/// Below this light level the player is in the dark and gets hurt
+pub const DARK_LEVEL: f32 = 0.25;
+
+/// Health lost per second in the dark, and regained per second in the light
+const DARKNESS_DAMAGE: f32 = 1.5;
+const RECOVERY_RATE: f32 = 3.0;
+
+/// How much light a held torch gives the player
+const TORCH_LIGHT: f32 = 0.6;
+
+/// Light level around the player, shown in the UI
+#[derive(Resource, Default)]
+pub struct PlayerLight(pub f32);
+
+/// Hurt the player while they stand in the dark - down a cave or outside at night -
+/// unless they carry a torch. Running out of health puts them back on the surface.
+pub fn darkness_damage(
+    mut player: ResMut<Player>,
+    mut player_light: ResMut<PlayerLight>,
+    world: Res<PixelWorld>,
+    clock: Res<DayNightClock>,
+    current_tool: Res<CurrentTool>,
+    time: Res<Time>,
+) {
+    let mut light = light_level(&world, &clock, player.x as i32, player.y as i32);
+    if current_tool.tool == Tool::Torch {
+        light = light.max(TORCH_LIGHT);
+    }
+    player_light.0 = light;
+
+    let dt = time.delta_secs();
+    if light < DARK_LEVEL {
+        player.health -= DARKNESS_DAMAGE * dt;
+    } else {
+        player.health = (player.health + RECOVERY_RATE * dt).min(MAX_HEALTH);
+    }
+
+    if player.health <= 0.0 {
+        warn!("Something in the dark got the player - respawning on the surface");
+        player.health = MAX_HEALTH;
+        player.vx = 0.0;
+        player.vy = 0.0;
+        player.move_to_surface(&world);
+    }
+}
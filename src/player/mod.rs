@@ -1,4 +1,6 @@
+pub mod camera;
 pub mod components;
+pub mod darkness;
 pub mod movement;
 pub mod rendering;
 
@@ -9,17 +11,17 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app
+            .init_resource::<darkness::PlayerLight>()
             .add_systems(Startup, (
-                setup_camera,
-                components::spawn_player,
+                components::spawn_player.after(crate::world::streaming::setup_world),
+                camera::setup_camera.after(components::spawn_player),
             ))
             .add_systems(Update, (
                 movement::player_movement,
                 rendering::render_player,
+                camera::zoom_camera,
+                camera::follow_player.after(movement::player_movement),
+                darkness::darkness_damage.after(movement::player_movement),
             ));
     }
 }
-
-fn setup_camera(mut commands: Commands) {
-    commands.spawn(Camera2d);
-}
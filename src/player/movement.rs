@@ -96,18 +96,23 @@ pub fn player_movement(
     player.y = final_y;
 }
 
+/// Ground materials (dirt, sand, clay, stone, ...) are the only things the player collides with
+fn is_ground(material: Material) -> bool {
+    material.props().ground
+}
+
 fn check_horizontal_collision(world: &PixelWorld, player: &Player, new_x: i32) -> bool {
-    // Only collide with ground (dirt) - can pass through trees and sand
+    // Only collide with ground - can pass through trees and water
     for dy in 0..player.height {
         let check_y = player.y as i32 + dy - player.height / 2;
 
         // Left side
-        if world.get(new_x - player.width / 2 - 1, check_y) == Material::Dirt {
+        if is_ground(world.get(new_x - player.width / 2 - 1, check_y)) {
             return false;
         }
 
         // Right side (symmetric with left)
-        if world.get(new_x + player.width / 2 + 1, check_y) == Material::Dirt {
+        if is_ground(world.get(new_x + player.width / 2 + 1, check_y)) {
             return false;
         }
     }
@@ -115,12 +120,12 @@ fn check_horizontal_collision(world: &PixelWorld, player: &Player, new_x: i32) -
 }
 
 fn check_ground_collision(world: &PixelWorld, player: &Player, x: i32, y: i32) -> bool {
-    // Only stand on dirt - can pass through trees and sand
+    // Only stand on ground - can pass through trees and water
     for dx in 0..player.width {
         let check_x = x + dx - player.width / 2;
         let check_y = y + player.height / 2 + 1;
 
-        if world.get(check_x, check_y) == Material::Dirt {
+        if is_ground(world.get(check_x, check_y)) {
             return true;
         }
     }
@@ -128,12 +133,12 @@ fn check_ground_collision(world: &PixelWorld, player: &Player, x: i32, y: i32) -
 }
 
 fn check_ceiling_collision(world: &PixelWorld, player: &Player, x: i32, y: i32) -> bool {
-    // Only collide with dirt ceiling - can pass through trees and sand
+    // Only collide with ground ceiling - can pass through trees and water
     for dx in 0..player.width {
         let check_x = x + dx - player.width / 2;
         let check_y = y - player.height / 2 - 1;
 
-        if world.get(check_x, check_y) == Material::Dirt {
+        if is_ground(world.get(check_x, check_y)) {
             return true;
         }
     }
@@ -153,8 +158,8 @@ fn check_slope(world: &PixelWorld, player: &Player, new_x: i32) -> Option<i32> {
             let check_y = test_y + dy - player.height / 2;
 
             // Check left and right sides at the new position
-            if world.get(new_x - player.width / 2 - 1, check_y) == Material::Dirt ||
-               world.get(new_x + player.width / 2 + 1, check_y) == Material::Dirt {
+            if is_ground(world.get(new_x - player.width / 2 - 1, check_y)) ||
+               is_ground(world.get(new_x + player.width / 2 + 1, check_y)) {
                 has_horizontal_space = false;
                 break;
             }
@@ -166,7 +171,7 @@ fn check_slope(world: &PixelWorld, player: &Player, new_x: i32) -> Option<i32> {
             let check_x = new_x + dx - player.width / 2;
             let check_y = test_y - player.height / 2 - 1;
 
-            if world.get(check_x, check_y) == Material::Dirt {
+            if is_ground(world.get(check_x, check_y)) {
                 has_ceiling = true;
                 break;
             }
@@ -0,0 +1,453 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::physics::collider_update::{chunk_collider, chunk_mass};
+use crate::physics::components::PixelBody;
+use crate::player::components::Player;
+use crate::tools::components::{CurrentTool, GrabbedChunk, Tool};
+use crate::world::chunk::{decode_runs, Chunk, EncodedChunk, CHUNK_SIZE};
+use crate::world::ground_colliders::GroundCollider;
+use crate::world::lighting::DayNightClock;
+use crate::world::particles::Particle;
+use crate::world::streaming::{chunk_column_at, material_names, material_remap, update_loaded_chunks};
+use crate::world::{ChunkStore, Material, MaterialRegistry, PixelWorld, TerrainGenerator, WorldSeed, WorldService};
+
+/// Current save format version. Bump it and add a step to `migrate` whenever the format changes.
+pub const SAVE_VERSION: u32 = 2;
+
+/// What to include in a save beyond the world itself
+#[derive(Clone, Copy, Default)]
+pub struct SaveOptions {
+    /// Also save short-lived visual particles
+    pub include_particles: bool,
+}
+
+/// A complete snapshot of the game, as written to disk
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    /// Material names indexed by the ids used in this file, so saves survive
+    /// materials being added or reordered in `assets/materials.json`
+    pub materials: Vec<String>,
+    /// Seed the world was originally generated from
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub world: SavedWorld,
+    /// Time of day, 0 at midnight
+    #[serde(default)]
+    pub time_of_day: Option<f32>,
+    pub player: Player,
+    pub tool: Tool,
+    pub bodies: Vec<SavedBody>,
+    #[serde(default)]
+    pub particles: Vec<SavedParticle>,
+}
+
+/// Every chunk that differs from what the seed generates - the rest is regenerated on load
+#[derive(Serialize, Deserialize)]
+pub struct SavedWorld {
+    pub chunks: Vec<EncodedChunk>,
+}
+
+/// A rigid body broken off from the pixel world
+#[derive(Serialize, Deserialize)]
+pub struct SavedBody {
+    pub pixels: Vec<(i32, i32, Material)>,
+    pub translation: [f32; 3],
+    pub rotation: f32,
+    pub linvel: [f32; 2],
+    pub angvel: f32,
+    pub gravity_scale: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    /// Pixel the body's transform sits on; saves from before it was stored used the
+    /// average pixel position
+    #[serde(default)]
+    pub origin: Option<[i32; 2]>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedParticle {
+    pub translation: [f32; 3],
+    pub velocity: [f32; 2],
+    pub lifetime: f32,
+    pub max_lifetime: f32,
+    pub color: [f32; 4],
+    pub size: [f32; 2],
+}
+
+impl SaveGame {
+    /// Snapshot the current game state
+    pub fn capture(world: &mut World, options: SaveOptions) -> Self {
+        let materials = material_names(MaterialRegistry::global());
+
+        // Modified chunks that are loaded, plus the ones streamed out to the chunk store
+        let pixel_world = world.resource::<PixelWorld>();
+        let mut chunks: Vec<EncodedChunk> = pixel_world
+            .chunks()
+            .filter(|(_, chunk)| chunk.modified)
+            .map(|(pos, chunk)| EncodedChunk::encode(pos, chunk))
+            .collect();
+        for stored in world.resource::<ChunkStore>().all_encoded() {
+            if !pixel_world.is_chunk_loaded(IVec2::new(stored.x, stored.y)) {
+                chunks.push(stored);
+            }
+        }
+
+        let player = world.resource::<Player>().clone();
+        let tool = world.resource::<CurrentTool>().tool;
+
+        let mut chunk_query = world.query::<(
+            &PixelBody,
+            &Transform,
+            &Velocity,
+            Option<&GravityScale>,
+            Option<&Damping>,
+        )>();
+        let bodies = chunk_query
+            .iter(world)
+            .filter(|(chunk, ..)| !chunk.pixels.is_empty())
+            .map(|(chunk, transform, velocity, gravity, damping)| SavedBody {
+                pixels: chunk.pixels.clone(),
+                translation: transform.translation.to_array(),
+                rotation: transform.rotation.to_euler(EulerRot::XYZ).2,
+                linvel: velocity.linvel.to_array(),
+                angvel: velocity.angvel,
+                gravity_scale: gravity.map_or(1.0, |g| g.0),
+                linear_damping: damping.map_or(0.0, |d| d.linear_damping),
+                angular_damping: damping.map_or(0.0, |d| d.angular_damping),
+                origin: Some(chunk.origin.to_array()),
+            })
+            .collect();
+
+        let particles = if options.include_particles {
+            let mut particle_query = world.query::<(&Particle, &Transform, &Sprite)>();
+            particle_query
+                .iter(world)
+                .map(|(particle, transform, sprite)| SavedParticle {
+                    translation: transform.translation.to_array(),
+                    velocity: particle.velocity.to_array(),
+                    lifetime: particle.lifetime,
+                    max_lifetime: particle.max_lifetime,
+                    color: sprite.color.to_srgba().to_f32_array(),
+                    size: sprite.custom_size.unwrap_or(Vec2::ONE).to_array(),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            version: SAVE_VERSION,
+            materials,
+            seed: Some(world.resource::<WorldSeed>().0),
+            world: SavedWorld { chunks },
+            time_of_day: Some(world.resource::<DayNightClock>().time),
+            player,
+            tool,
+            bodies,
+            particles,
+        }
+    }
+
+    /// Replace the current game state with this snapshot
+    pub fn apply(self, world: &mut World) -> Result<(), String> {
+        // Map this file's material ids onto the ones currently registered
+        let remap = material_remap(MaterialRegistry::global(), &self.materials);
+
+        // Decode everything up front so a broken save leaves the current game untouched
+        let chunks = self.world.chunks
+            .iter()
+            .map(|chunk| chunk.decode(&remap))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Clear out everything that belongs to the old game
+        let mut old = world.query_filtered::<Entity, Or<(With<PixelBody>, With<Particle>, With<GroundCollider>)>>();
+        for entity in old.iter(world).collect::<Vec<_>>() {
+            world.despawn(entity);
+        }
+
+        // Saved chunks go into the chunk store for the save's seed, so they're streamed in like
+        // any other modified chunk
+        world.resource::<ChunkStore>().clear();
+        let seed = self.seed.map_or(*world.resource::<WorldSeed>(), WorldSeed);
+        let store = ChunkStore::for_seed(seed.0);
+        store.clear();
+        for (pos, chunk) in &chunks {
+            store.save(*pos, chunk)?;
+        }
+
+        let mut pixel_world = PixelWorld::new(world.resource::<PixelWorld>().height);
+        let generator = TerrainGenerator::new(seed.0, pixel_world.height);
+        let player_x = WorldService::pixel_to_world(self.player.x as i32, 0).x;
+        update_loaded_chunks(&mut pixel_world, &generator, &store, chunk_column_at(player_x));
+
+        // Every chunk comes in with a stale outline, so ground colliders are rebuilt before
+        // the next physics step and loaded bodies have something to land on
+        world.insert_resource(pixel_world);
+        world.insert_resource(seed);
+        world.insert_resource(store);
+        world.insert_resource(generator);
+        world.insert_resource(self.player);
+        world.insert_resource(CurrentTool { tool: self.tool });
+        if let Some(time) = self.time_of_day {
+            world.insert_resource(DayNightClock { time });
+        }
+        world.insert_resource(GrabbedChunk::default());
+
+        for chunk in self.bodies {
+            let pixels: Vec<(i32, i32, Material)> = chunk.pixels
+                .into_iter()
+                .map(|(x, y, material)| (x, y, remap(material)))
+                .collect();
+            if pixels.is_empty() {
+                continue;
+            }
+            let count = pixels.len() as i32;
+            let origin = match chunk.origin {
+                Some(origin) => IVec2::from_array(origin),
+                None => pixels.iter().fold(IVec2::ZERO, |sum, (x, y, _)| sum + IVec2::new(*x, *y)) / count,
+            };
+            let body = PixelBody { pixels, origin };
+
+            world.spawn((
+                Transform::from_translation(Vec3::from_array(chunk.translation))
+                    .with_rotation(Quat::from_rotation_z(chunk.rotation)),
+                RigidBody::Dynamic,
+                chunk_collider(&body),
+                chunk_mass(&body),
+                Velocity {
+                    linvel: Vec2::from_array(chunk.linvel),
+                    angvel: chunk.angvel,
+                },
+                GravityScale(chunk.gravity_scale),
+                Restitution::coefficient(0.3),
+                Friction::coefficient(0.8),
+                Damping {
+                    linear_damping: chunk.linear_damping,
+                    angular_damping: chunk.angular_damping,
+                },
+                body,
+            ));
+        }
+
+        for particle in self.particles {
+            let [r, g, b, a] = particle.color;
+            world.spawn((
+                Sprite {
+                    color: Color::srgba(r, g, b, a),
+                    custom_size: Some(Vec2::from_array(particle.size)),
+                    ..default()
+                },
+                Transform::from_translation(Vec3::from_array(particle.translation)),
+                Particle {
+                    velocity: Vec2::from_array(particle.velocity),
+                    lifetime: particle.lifetime,
+                    max_lifetime: particle.max_lifetime,
+                },
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| e.to_string())
+    }
+
+    /// Parse a save, upgrading it from older format versions first
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let value = migrate(value)?;
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+}
+
+/// Bring a save written by an older version up to `SAVE_VERSION`
+fn migrate(save: serde_json::Value) -> Result<serde_json::Value, String> {
+    let version = save
+        .get("version")
+        .and_then(|v| v.as_u64())
+        .ok_or("save has no version header")?;
+
+    if version > SAVE_VERSION as u64 {
+        return Err(format!("save version {} is newer than this game supports ({})", version, SAVE_VERSION));
+    }
+
+    let mut save = save;
+    if version < 2 {
+        save = migrate_v1(save)?;
+    }
+
+    // Each format change adds a step here that upgrades `save` from the version before it
+    Ok(save)
+}
+
+/// v1 saved one fixed 800x600 grid; v2 saves chunks of an unbounded world.
+/// The old grid is cut into chunks, with anything outside it left for the generator.
+fn migrate_v1(mut save: serde_json::Value) -> Result<serde_json::Value, String> {
+    #[derive(Deserialize)]
+    struct GridV1 {
+        width: usize,
+        height: usize,
+        pixels: Vec<(Material, u32)>,
+        lifetimes: Vec<(u16, u32)>,
+    }
+
+    let grid: GridV1 = serde_json::from_value(save["world"].take()).map_err(|e| e.to_string())?;
+    let len = grid.width * grid.height;
+    let pixels = decode_runs(&grid.pixels, len)?;
+    let lifetimes = decode_runs(&grid.lifetimes, len)?;
+
+    let (width, height) = (grid.width as i32, grid.height as i32);
+    let mut chunks = Vec::new();
+    for chunk_y in 0..(height + CHUNK_SIZE - 1) / CHUNK_SIZE {
+        for chunk_x in 0..(width + CHUNK_SIZE - 1) / CHUNK_SIZE {
+            let pos = IVec2::new(chunk_x, chunk_y);
+            let mut chunk = Chunk::default();
+            for local_y in 0..CHUNK_SIZE {
+                for local_x in 0..CHUNK_SIZE {
+                    let (x, y) = (chunk_x * CHUNK_SIZE + local_x, chunk_y * CHUNK_SIZE + local_y);
+                    let local = (local_y * CHUNK_SIZE + local_x) as usize;
+                    if x >= width {
+                        continue;
+                    }
+                    // The old world was shorter - extend its bottom row down
+                    let index = (y.min(height - 1) * width + x) as usize;
+                    chunk.pixels[local] = pixels[index];
+                    if y < height {
+                        chunk.lifetimes[local] = lifetimes[index];
+                    }
+                }
+            }
+            chunk.reset_shades(pos);
+            chunks.push(EncodedChunk::encode(pos, &chunk));
+        }
+    }
+
+    save["world"] = serde_json::json!({ "chunks": chunks });
+    save["bodies"] = save["chunks"].take();
+    save["version"] = 2.into();
+    Ok(save)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::platform::collections::HashMap;
+
+    fn names() -> Vec<String> {
+        material_names(MaterialRegistry::global())
+    }
+
+    fn decode(save: &SaveGame) -> Vec<(IVec2, Chunk)> {
+        let remap = material_remap(MaterialRegistry::global(), &save.materials);
+        save.world.chunks.iter().map(|chunk| chunk.decode(&remap).unwrap()).collect()
+    }
+
+    #[test]
+    fn save_comes_back_the_same_from_json() {
+        let pos = IVec2::new(-2, 17);
+        let mut chunk = Chunk::default();
+        chunk.reset_shades(pos);
+        for index in 0..200 {
+            chunk.pixels[index] = Material::DIRT;
+        }
+        chunk.pixels[300] = Material::FIRE;
+        chunk.lifetimes[300] = 42;
+        chunk.pixels[301] = Material::SAND;
+        chunk.shades[301] = 3;
+
+        let save = SaveGame {
+            version: SAVE_VERSION,
+            materials: names(),
+            seed: Some(1234),
+            world: SavedWorld { chunks: vec![EncodedChunk::encode(pos, &chunk)] },
+            time_of_day: Some(0.6),
+            player: Player { x: 10.5, ..default() },
+            tool: Tool::Axe,
+            bodies: vec![SavedBody {
+                pixels: vec![(3, 4, Material::WOOD), (3, 5, Material::LEAF)],
+                translation: [1.5, -2.5, 1.0],
+                rotation: 0.25,
+                linvel: [3.0, -4.0],
+                angvel: 0.5,
+                gravity_scale: 5.0,
+                linear_damping: 0.1,
+                angular_damping: 0.8,
+                origin: Some([3, 4]),
+            }],
+            particles: Vec::new(),
+        };
+
+        let loaded = SaveGame::from_json(&save.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.version, SAVE_VERSION);
+        assert_eq!(loaded.seed, Some(1234));
+        assert_eq!(loaded.time_of_day, Some(0.6));
+        assert_eq!(loaded.player.x, 10.5);
+        assert_eq!(loaded.tool, Tool::Axe);
+
+        let chunks = decode(&loaded);
+        assert_eq!(chunks.len(), 1);
+        let (loaded_pos, loaded_chunk) = &chunks[0];
+        assert_eq!(*loaded_pos, pos);
+        assert_eq!(loaded_chunk.pixels, chunk.pixels);
+        assert_eq!(loaded_chunk.lifetimes, chunk.lifetimes);
+        assert_eq!(loaded_chunk.shades, chunk.shades);
+
+        let body = &loaded.bodies[0];
+        assert_eq!(body.pixels, save.bodies[0].pixels);
+        assert_eq!(body.origin, Some([3, 4]));
+        assert_eq!(body.translation, [1.5, -2.5, 1.0]);
+        assert_eq!((body.rotation, body.linvel, body.angvel), (0.25, [3.0, -4.0], 0.5));
+    }
+
+    #[test]
+    fn v1_grid_is_cut_into_chunks() {
+        // A 40x20 grid: air over dirt, with one burning pixel
+        let fire = 5 * 40 + 8;
+        let v1 = serde_json::json!({
+            "version": 1,
+            "materials": names(),
+            "world": {
+                "width": 40,
+                "height": 20,
+                "pixels": [[Material::AIR, fire], [Material::FIRE, 1], [Material::AIR, 400 - fire - 1], [Material::DIRT, 400]],
+                "lifetimes": [[0, fire], [30, 1], [0, 800 - fire - 1]],
+            },
+            "player": Player::default(),
+            "tool": Tool::Shovel,
+            "chunks": [{
+                "pixels": [[3, 4, Material::WOOD]],
+                "translation": [0.0, 0.0, 1.0],
+                "rotation": 0.0,
+                "linvel": [0.0, 0.0],
+                "angvel": 0.0,
+                "gravity_scale": 5.0,
+                "linear_damping": 0.1,
+                "angular_damping": 0.8,
+            }],
+        });
+
+        let save = SaveGame::from_json(&v1.to_string()).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.tool, Tool::Shovel);
+        assert_eq!(save.bodies.len(), 1);
+        assert_eq!(save.bodies[0].pixels, vec![(3, 4, Material::WOOD)]);
+        assert_eq!(save.bodies[0].origin, None);
+
+        let chunks: HashMap<IVec2, Chunk> = decode(&save).into_iter().collect();
+        assert_eq!(chunks.len(), 2);
+        let pixel = |x: i32, y: i32| {
+            let chunk = &chunks[&IVec2::new(x / CHUNK_SIZE, y / CHUNK_SIZE)];
+            let index = ((y % CHUNK_SIZE) * CHUNK_SIZE + x % CHUNK_SIZE) as usize;
+            (chunk.pixels[index], chunk.lifetimes[index])
+        };
+        assert_eq!(pixel(8, 5), (Material::FIRE, 30));
+        assert_eq!(pixel(20, 5), (Material::AIR, 0));
+        assert_eq!(pixel(39, 15), (Material::DIRT, 0));
+        // The bottom row carries on down the taller world, and past the old right edge is left empty
+        assert_eq!(pixel(20, 31), (Material::DIRT, 0));
+        assert_eq!(pixel(45, 15), (Material::AIR, 0));
+    }
+}
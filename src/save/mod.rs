@@ -0,0 +1,51 @@
+pub mod format;
+
+pub use format::{SaveGame, SaveOptions};
+
+use bevy::prelude::*;
+use std::fs;
+use std::path::Path;
+
+/// File used by the quicksave/quickload hotkeys
+pub const QUICKSAVE_FILE: &str = "quicksave.json";
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, handle_save_hotkeys);
+    }
+}
+
+/// Save the current game to a file
+pub fn save_to_file(world: &mut World, path: impl AsRef<Path>, options: SaveOptions) -> Result<(), String> {
+    let json = SaveGame::capture(world, options).to_json()?;
+    fs::write(path, json).map_err(|e| e.to_string())
+}
+
+/// Replace the current game with one loaded from a file
+pub fn load_from_file(world: &mut World, path: impl AsRef<Path>) -> Result<(), String> {
+    let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    SaveGame::from_json(&json)?.apply(world)
+}
+
+/// F5 to quicksave, F9 to quickload
+fn handle_save_hotkeys(world: &mut World) {
+    let keyboard = world.resource::<ButtonInput<KeyCode>>();
+    let save = keyboard.just_pressed(KeyCode::F5);
+    let load = keyboard.just_pressed(KeyCode::F9);
+
+    if save {
+        match save_to_file(world, QUICKSAVE_FILE, SaveOptions { include_particles: true }) {
+            Ok(()) => info!("Saved game to {}", QUICKSAVE_FILE),
+            Err(e) => warn!("Failed to save game: {}", e),
+        }
+    }
+
+    if load {
+        match load_from_file(world, QUICKSAVE_FILE) {
+            Ok(()) => info!("Loaded game from {}", QUICKSAVE_FILE),
+            Err(e) => warn!("Failed to load {}: {}", QUICKSAVE_FILE, e),
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Tool {
     Hand,
     Axe,
     Shovel,
+    Torch,
 }
 
 impl Tool {
@@ -13,37 +15,21 @@ impl Tool {
             Tool::Hand => "Hand",
             Tool::Axe => "Axe",
             Tool::Shovel => "Shovel",
-        }
-    }
-
-    pub fn color(&self) -> Color {
-        match self {
-            Tool::Hand => Color::srgb(0.9, 0.8, 0.7),
-            Tool::Axe => Color::srgb(0.6, 0.3, 0.1),
-            Tool::Shovel => Color::srgb(0.5, 0.5, 0.5),
+            Tool::Torch => "Torch",
         }
     }
 
     /// Check if this tool can break the given material
     pub fn can_break(&self, material: &crate::world::Material) -> bool {
-        use crate::world::Material;
-
-        match (self, material) {
-            (Tool::Axe, Material::Wood) => true,
-            (Tool::Axe, Material::Leaf) => true, // Axe can cut leaves too
-            (Tool::Axe, Material::Fiber) => true, // Axe can harvest fiber bushes
-            (Tool::Shovel, Material::Dirt) => true,
-            (Tool::Shovel, Material::Sand) => true,
-            (Tool::Hand, Material::Fiber) => true, // Can pick fiber by hand
-            _ => false,
-        }
+        material.props().breakable_by.contains(self)
     }
 
     pub fn next(&self) -> Self {
         match self {
             Tool::Hand => Tool::Axe,
             Tool::Axe => Tool::Shovel,
-            Tool::Shovel => Tool::Hand,
+            Tool::Shovel => Tool::Torch,
+            Tool::Torch => Tool::Hand,
         }
     }
 }
@@ -1,7 +1,8 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use super::components::{CurrentTool, Tool, GrabbedChunk};
-use crate::physics::components::WoodChunk;
+use crate::physics::components::PixelBody;
+use crate::world::WorldService;
 
 pub fn handle_hand_tool(
     current_tool: Res<CurrentTool>,
@@ -10,7 +11,7 @@ pub fn handle_hand_tool(
     keyboard: Res<ButtonInput<KeyCode>>,
     windows: Query<&Window>,
     camera_query: Query<(&Camera, &GlobalTransform)>,
-    mut chunk_query: Query<(Entity, &Transform, &mut Velocity), With<WoodChunk>>,
+    mut chunk_query: Query<(Entity, &Transform, &mut Velocity), With<PixelBody>>,
 ) {
     // Only use hand when Hand tool is selected
     if current_tool.tool != Tool::Hand {
@@ -22,23 +23,7 @@ pub fn handle_hand_tool(
     }
 
     // Get mouse position in world
-    let world_pos = if let Ok(window) = windows.single() {
-        if let Some(cursor_pos) = window.cursor_position() {
-            if let Ok((camera, camera_transform)) = camera_query.single() {
-                if let Ok(ray) = camera.viewport_to_world(camera_transform, cursor_pos) {
-                    Some(ray.origin.truncate())
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    } else {
-        None
-    };
+    let world_pos = WorldService::cursor_to_world(&windows, &camera_query);
 
     if let Some(world_pos) = world_pos {
         // On mouse press, try to grab a chunk
@@ -1,8 +1,8 @@
 use bevy::prelude::*;
 use rand::Rng;
-use super::components::CurrentTool;
-use crate::world::{PixelWorld, Material, WorldService, ParticleSpawnEvent};
-use crate::physics::components::WoodChunk;
+use super::components::{CurrentTool, Tool};
+use crate::world::{fire, PixelWorld, Material, WorldService, ParticleSpawnEvent};
+use crate::physics::components::PixelBody;
 
 /// Cooldown timer to prevent spawning too many particles
 #[derive(Resource)]
@@ -32,6 +32,7 @@ impl Default for BlockBreakTimer {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn use_tool(
     mut commands: Commands,
     current_tool: Res<CurrentTool>,
@@ -39,7 +40,7 @@ pub fn use_tool(
     mouse_buttons: Res<ButtonInput<MouseButton>>,
     windows: Query<&Window>,
     camera_query: Query<(&Camera, &GlobalTransform)>,
-    mut chunk_query: Query<(Entity, &Transform, &mut WoodChunk)>,
+    mut chunk_query: Query<(Entity, &Transform, &mut PixelBody)>,
     mut particle_timer: ResMut<ParticleSpawnTimer>,
     mut break_timer: ResMut<BlockBreakTimer>,
     mut particle_events: EventWriter<ParticleSpawnEvent>,
@@ -63,22 +64,22 @@ pub fn use_tool(
     }
 
     // Get mouse position in world
-    if let Ok(window) = windows.single() {
-        if let Some(cursor_pos) = window.cursor_position() {
-            if let Ok((camera, camera_transform)) = camera_query.single() {
-                if let Ok(ray) = camera.viewport_to_world(camera_transform, cursor_pos) {
-                    let world_pos = ray.origin.truncate();
-                    let (pixel_x, pixel_y) = WorldService::world_to_pixel(world_pos);
-
-                    // Use tool to break blocks in the pixel world
-                    use_tool_at_position(&mut world, &current_tool.tool, pixel_x, pixel_y, should_spawn_particles, &mut particle_events);
-
-                    // Also break pixels in wood chunks (felled trees)
-                    use_tool_on_chunks(&mut commands, &current_tool.tool, world_pos, &mut chunk_query, should_spawn_particles, &mut particle_events);
-                }
-            }
-        }
+    let Some(world_pos) = WorldService::cursor_to_world(&windows, &camera_query) else {
+        return;
+    };
+    let (pixel_x, pixel_y) = WorldService::world_to_pixel(world_pos);
+
+    // Torch sets things alight instead of breaking them
+    if current_tool.tool == Tool::Torch {
+        use_torch_at_position(&mut world, pixel_x, pixel_y);
+        return;
     }
+
+    // Use tool to break blocks in the pixel world
+    use_tool_at_position(&mut world, &current_tool.tool, pixel_x, pixel_y, should_spawn_particles, &mut particle_events);
+
+    // Also break pixels in falling bodies (felled trees, collapsed dirt)
+    use_tool_on_chunks(&mut commands, &current_tool.tool, world_pos, &mut chunk_query, should_spawn_particles, &mut particle_events);
 }
 
 fn use_tool_at_position(
@@ -105,9 +106,9 @@ fn use_tool_at_position(
                 let material = world.get(check_x, check_y);
 
                 // Only destroy if the current tool can break this material
-                // Add 30% chance to miss individual pixels for more randomness
-                if tool.can_break(&material) && rng.gen_bool(0.7) {
-                    world.set(check_x, check_y, Material::Air);
+                // Harder materials make the tool miss more individual pixels
+                if tool.can_break(&material) && rng.gen_bool(material.props().break_chance()) {
+                    world.set(check_x, check_y, Material::AIR);
 
                     // Collect broken material for particle spawning
                     if should_spawn_particles {
@@ -124,7 +125,7 @@ fn use_tool_at_position(
         // Group broken materials by type
         let mut material_groups: std::collections::HashMap<Material, Vec<Vec2>> = std::collections::HashMap::new();
         for (material, pos) in broken_materials {
-            material_groups.entry(material).or_insert_with(Vec::new).push(pos);
+            material_groups.entry(material).or_default().push(pos);
         }
 
         // Send particle spawn events for each material type
@@ -144,34 +145,40 @@ fn use_tool_at_position(
     }
 }
 
+/// Ignite flammable pixels around the cursor and light flames in the air
+fn use_torch_at_position(world: &mut PixelWorld, x: i32, y: i32) {
+    let mut rng = rand::thread_rng();
+    let torch_radius = 3;
+
+    for dy in -torch_radius..=torch_radius {
+        for dx in -torch_radius..=torch_radius {
+            if dx * dx + dy * dy > torch_radius * torch_radius {
+                continue;
+            }
+
+            let check_x = x + dx;
+            let check_y = y + dy;
+            if !fire::ignite(world, check_x, check_y) && rng.gen_bool(0.5) {
+                fire::spawn_flame(world, &mut rng, check_x, check_y);
+            }
+        }
+    }
+}
+
 fn use_tool_on_chunks(
     _commands: &mut Commands,
     tool: &super::components::Tool,
     world_pos: Vec2,
-    chunk_query: &mut Query<(Entity, &Transform, &mut WoodChunk)>,
+    chunk_query: &mut Query<(Entity, &Transform, &mut PixelBody)>,
     should_spawn_particles: bool,
     particle_events: &mut EventWriter<ParticleSpawnEvent>,
 ) {
-    use super::components::Tool;
-
-    // Only axe can cut wood chunks
-    if *tool != Tool::Axe {
-        return;
-    }
-
     let mut rng = rand::thread_rng();
     // Random smaller radius (1.0-3.0 pixels instead of fixed 5.0)
     let tool_radius = rng.gen_range(1.0..=3.0);
-    let mut removed_positions: Vec<Vec2> = Vec::new();
+    let mut removed_positions: Vec<(Vec2, Material)> = Vec::new();
 
     for (_entity, transform, mut chunk) in chunk_query.iter_mut() {
-        // Calculate center offset (same as rendering)
-        let sum_x: i32 = chunk.pixels.iter().map(|(x, _, _)| x).sum();
-        let sum_y: i32 = chunk.pixels.iter().map(|(_, y, _)| y).sum();
-        let count = chunk.pixels.len() as i32;
-        let center_x = sum_x / count;
-        let center_y = sum_y / count;
-
         let chunk_pos = transform.translation.truncate();
         let rotation = transform.rotation.to_euler(bevy::math::EulerRot::XYZ).2;
         let cos = rotation.cos();
@@ -183,26 +190,26 @@ fn use_tool_on_chunks(
 
         for (px, py, material) in chunk.pixels.iter() {
             // Transform pixel position to world space (same as rendering)
-            let offset_x = (*px - center_x) as f32;
-            let offset_y = -(*py - center_y) as f32; // Negative because screen y is flipped
-
-            let rotated_x = offset_x * cos - offset_y * sin;
-            let rotated_y = offset_x * sin + offset_y * cos;
+            let offset = chunk.local_offset(*px, *py);
+            let rotated_x = offset.x * cos - offset.y * sin;
+            let rotated_y = offset.x * sin + offset.y * cos;
 
             let world_pixel_pos = Vec2::new(
                 chunk_pos.x + rotated_x,
                 chunk_pos.y + rotated_y,
             );
 
-            // Keep pixel if it's outside tool radius or random miss (30% chance)
+            // Keep pixel if it's outside tool radius, the tool can't break it, or a random miss
             let distance = world_pixel_pos.distance(world_pos);
             let in_radius = distance <= tool_radius;
-            let should_remove = in_radius && rng.gen_bool(0.7); // 70% chance to actually remove
+            let should_remove = in_radius
+                && tool.can_break(material)
+                && rng.gen_bool(material.props().break_chance());
 
             if should_remove {
                 // If we're removing this pixel, save its position for particles
                 if should_spawn_particles {
-                    removed_positions.push(world_pixel_pos);
+                    removed_positions.push((world_pixel_pos, *material));
                 }
             } else {
                 // Keep this pixel
@@ -210,19 +217,22 @@ fn use_tool_on_chunks(
             }
         }
 
-        chunk.pixels = new_pixels;
+        // Only touch bodies that lost pixels, so the rest aren't rebaked
+        let removed_count = original_len - new_pixels.len();
+        if removed_count > 0 {
+            chunk.pixels = new_pixels;
+        }
 
         // Spawn particles if we removed any pixels from this chunk
-        let removed_count = original_len - chunk.pixels.len();
         if should_spawn_particles && removed_count > 0 {
             // Send particle spawn events at 1-2 random removed positions (reduced from 2-4)
             let num_particle_spawns = rng.gen_range(1.min(removed_positions.len())..=2.min(removed_positions.len()));
 
             for _ in 0..num_particle_spawns {
-                if let Some(pos) = removed_positions.get(rng.gen_range(0..removed_positions.len())) {
+                if let Some((pos, material)) = removed_positions.get(rng.gen_range(0..removed_positions.len())) {
                     particle_events.write(ParticleSpawnEvent {
                         position: *pos,
-                        material: Material::Wood,
+                        material: *material,
                     });
                 }
             }
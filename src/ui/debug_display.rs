@@ -25,7 +25,6 @@ pub fn setup_debug_display(mut commands: Commands) {
 pub fn update_debug_display(
     mut query: Query<&mut Text, With<DebugText>>,
     debug_config: Res<crate::debug::DebugConfig>,
-    menu_visible: Res<crate::debug::DebugMenuVisible>,
     diagnostics: Res<DiagnosticsStore>,
 ) {
     let Ok(mut text) = query.single_mut() else {
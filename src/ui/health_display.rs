@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+use crate::player::components::Player;
+use crate::player::darkness::{PlayerLight, DARK_LEVEL};
+
+#[derive(Component)]
+pub struct HealthText;
+
+pub fn setup_health_display(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 20.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        },
+        HealthText,
+    ));
+}
+
+/// Show the player's health, turning red while the dark is hurting them
+pub fn update_health_display(
+    player: Res<Player>,
+    player_light: Res<PlayerLight>,
+    mut query: Query<(&mut Text, &mut TextColor), With<HealthText>>,
+) {
+    let Ok((mut text, mut color)) = query.single_mut() else {
+        return;
+    };
+
+    let in_dark = player_light.0 < DARK_LEVEL;
+    **text = if in_dark {
+        format!("Health: {:.0} - it's too dark!", player.health.max(0.0))
+    } else {
+        format!("Health: {:.0}", player.health)
+    };
+    color.0 = if in_dark { Color::srgb(1.0, 0.3, 0.3) } else { Color::WHITE };
+}
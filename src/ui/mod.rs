@@ -1,5 +1,6 @@
 pub mod tool_indicator;
 pub mod debug_display;
+pub mod health_display;
 
 use bevy::prelude::*;
 use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
@@ -12,11 +13,13 @@ impl Plugin for UiPlugin {
             .add_systems(Startup, (
                 tool_indicator::setup_tool_indicator,
                 debug_display::setup_debug_display,
+                health_display::setup_health_display,
             ))
             .add_systems(Update, (
                 tool_indicator::render_tool_indicator,
                 tool_indicator::update_tool_indicator_text,
                 debug_display::update_debug_display,
+                health_display::update_health_display,
             ));
     }
 }
@@ -58,6 +58,14 @@ pub fn render_tool_indicator(
                 Color::srgb(0.5, 0.5, 0.5),
             );
         }
+        Tool::Torch => {
+            // Draw torch icon
+            gizmos.rect_2d(
+                Isometry2d::new(position, Rot2::IDENTITY),
+                Vec2::new(20.0, 20.0),
+                Color::srgb(1.0, 0.5, 0.1),
+            );
+        }
     }
 }
 
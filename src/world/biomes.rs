@@ -0,0 +1,188 @@
+use super::materials::Material;
+use super::terrain::{feature_hash, unit_from_hash};
+use super::vegetation::TreeSpecies;
+
+/// Everything terrain generation needs to know about one kind of landscape.
+/// Add an entry to `BIOMES` to add a biome - the generator picks it up automatically.
+pub struct Biome {
+    pub name: &'static str,
+    /// How often this biome is picked relative to the others
+    pub weight: f32,
+    /// Average surface height in pixels from the top of the world
+    pub surface_level: f64,
+    /// How far broad hills rise and fall around `surface_level`
+    pub hill_height: f64,
+    /// Amplitude of small-scale bumps on top of the hills
+    pub roughness: f64,
+    /// Material layers under the surface as (material, thickness), top first.
+    /// Everything below the last layer is stone.
+    pub layers: &'static [(Material, f64)],
+    /// Noise threshold for iron ore in the stone - lower means more ore
+    pub ore_threshold: f64,
+    /// Fill dips below `surface_level` with water
+    pub flooded: bool,
+    pub tree: Option<TreeSpecies>,
+    /// Range of distances between neighbouring trees
+    pub tree_spacing: (i32, i32),
+    /// Average number of fiber bushes per 100 pixels of width
+    pub bushes_per_100px: f64,
+}
+
+pub const BIOMES: &[Biome] = &[
+    Biome {
+        name: "Beach",
+        weight: 1.0,
+        surface_level: 550.0,
+        hill_height: 10.0,
+        roughness: 1.0,
+        layers: &[(Material::SAND, 10.0), (Material::SANDSTONE, 6.0), (Material::DIRT, 8.0), (Material::CLAY, 4.0)],
+        ore_threshold: 0.7,
+        flooded: false,
+        tree: Some(TreeSpecies::Palm),
+        tree_spacing: (70, 140),
+        bushes_per_100px: 0.5,
+    },
+    Biome {
+        name: "Jungle Floor",
+        weight: 2.0,
+        surface_level: 515.0,
+        hill_height: 30.0,
+        roughness: 2.0,
+        layers: &[(Material::DIRT, 16.0), (Material::CLAY, 6.0)],
+        ore_threshold: 0.65,
+        flooded: false,
+        tree: Some(TreeSpecies::Jungle),
+        tree_spacing: (45, 80),
+        bushes_per_100px: 3.0,
+    },
+    Biome {
+        name: "Swamp",
+        weight: 1.0,
+        surface_level: 540.0,
+        hill_height: 8.0,
+        roughness: 1.5,
+        layers: &[(Material::DIRT, 3.0), (Material::CLAY, 16.0)],
+        ore_threshold: 0.7,
+        flooded: true,
+        tree: Some(TreeSpecies::Mangrove),
+        tree_spacing: (60, 110),
+        bushes_per_100px: 1.0,
+    },
+    Biome {
+        name: "Rocky Highland",
+        weight: 1.0,
+        surface_level: 470.0,
+        hill_height: 40.0,
+        roughness: 6.0,
+        layers: &[(Material::DIRT, 3.0), (Material::CLAY, 2.0)],
+        ore_threshold: 0.55,
+        flooded: false,
+        tree: Some(TreeSpecies::Pine),
+        tree_spacing: (80, 160),
+        bushes_per_100px: 0.5,
+    },
+];
+
+/// Average width of a biome region in pixels, and how far each boundary is shifted either way
+const REGION_WIDTH: i32 = 240;
+const REGION_JITTER: i32 = 60;
+
+/// Columns either side of a region boundary over which surface heights blend
+pub const BLEND_WIDTH: i32 = 40;
+
+/// Salt for hashing region boundaries and biome choices
+const REGION_SALT: u64 = 0xB10E;
+
+/// A horizontal run of columns belonging to one biome
+#[derive(Clone, Copy, Debug)]
+pub struct BiomeRegion {
+    pub start: i32,
+    pub end: i32,
+    /// Index into `BIOMES`
+    pub biome: usize,
+}
+
+/// Which biome each column of the world belongs to.
+/// Regions are derived from the seed alone, so any column can be looked up
+/// without generating the ones before it. Neighbouring regions can share a
+/// biome, which just makes a wider region.
+pub struct BiomeMap {
+    seed: u64,
+}
+
+impl BiomeMap {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Left edge of region `index`
+    fn boundary(&self, index: i32) -> i32 {
+        let hash = feature_hash(self.seed, REGION_SALT, index as i64);
+        let jitter = (hash % (2 * REGION_JITTER as u64 + 1)) as i32 - REGION_JITTER;
+        index * REGION_WIDTH + jitter
+    }
+
+    fn biome_for(&self, index: i32) -> usize {
+        let roll = unit_from_hash(feature_hash(self.seed, REGION_SALT + 1, index as i64));
+        let total: f32 = BIOMES.iter().map(|biome| biome.weight).sum();
+        let mut target = roll as f32 * total;
+        for (i, biome) in BIOMES.iter().enumerate() {
+            if target < biome.weight {
+                return i;
+            }
+            target -= biome.weight;
+        }
+        BIOMES.len() - 1
+    }
+
+    pub fn region_at(&self, x: i32) -> BiomeRegion {
+        let mut index = x.div_euclid(REGION_WIDTH);
+        if x < self.boundary(index) {
+            index -= 1;
+        } else if x >= self.boundary(index + 1) {
+            index += 1;
+        }
+        BiomeRegion {
+            start: self.boundary(index),
+            end: self.boundary(index + 1),
+            biome: self.biome_for(index),
+        }
+    }
+
+    pub fn biome_at(&self, x: i32) -> &'static Biome {
+        &BIOMES[self.region_at(x).biome]
+    }
+
+    /// Biomes contributing to a column and their blend weights, which sum to 1.
+    /// Columns near a region boundary are shared with the neighbouring biome.
+    pub fn blend_at(&self, x: i32) -> Vec<(&'static Biome, f64)> {
+        let region = self.region_at(x);
+        let own = &BIOMES[region.biome];
+
+        let from_start = x - region.start;
+        let to_end = region.end - 1 - x;
+
+        let neighbour = if from_start < BLEND_WIDTH {
+            Some((self.region_at(region.start - 1).biome, from_start))
+        } else if to_end < BLEND_WIDTH {
+            Some((self.region_at(region.end).biome, to_end))
+        } else {
+            None
+        };
+
+        match neighbour {
+            Some((other, distance)) => {
+                // Half and half at the boundary, all ours BLEND_WIDTH columns in
+                let t = distance as f64 / BLEND_WIDTH as f64;
+                let weight = 0.5 + 0.5 * smoothstep(t);
+                vec![(own, weight), (&BIOMES[other], 1.0 - weight)]
+            }
+            None => vec![(own, 1.0)],
+        }
+    }
+}
+
+fn smoothstep(t: f64) -> f64 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
@@ -0,0 +1,219 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use super::lighting::Light;
+use super::materials::Material;
+use super::terrain::feature_hash;
+
+/// Width and height of a chunk in pixels
+pub const CHUNK_SIZE: i32 = 32;
+
+/// Salt for hashing pixel positions into shades
+const SHADE_SALT: u64 = 0x5AAD;
+
+/// Rows a shade streak runs for in materials with `grain`
+const GRAIN_LENGTH: i32 = 6;
+
+/// A square block of the pixel world, the unit of generation, rendering and streaming
+#[derive(Clone)]
+pub struct Chunk {
+    pub pixels: Vec<Material>,
+    /// Remaining ticks for short-lived pixels (fire, embers, smoke)
+    pub lifetimes: Vec<u16>,
+    /// Colour variation of each pixel, picking an entry from its material's palette
+    pub shades: Vec<u8>,
+    /// Light reaching each pixel, worked out by the lighting pass
+    pub light: Vec<Light>,
+    /// Changed since it was generated, so it has to be written to disk when unloaded
+    pub modified: bool,
+    /// Something in or next to the chunk changed, so it needs simulating next tick
+    pub awake: bool,
+    /// Pixels changed since the chunk's texture was last uploaded
+    pub dirty: bool,
+    /// Pixels changed since the chunk's lighting was last worked out
+    pub unlit: bool,
+    /// Ground changed in the chunk or along its seams since its ground collider was built
+    pub outline_stale: bool,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        let len = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+        Self {
+            pixels: vec![Material::AIR; len],
+            lifetimes: vec![0; len],
+            shades: vec![0; len],
+            light: vec![Light::default(); len],
+            modified: false,
+            awake: true,
+            dirty: true,
+            unlit: true,
+            outline_stale: true,
+        }
+    }
+}
+
+impl Chunk {
+    /// Give every pixel the shade it would get if it had been placed where it is
+    pub fn reset_shades(&mut self, pos: IVec2) {
+        for (index, (material, shade)) in self.pixels.iter().zip(&mut self.shades).enumerate() {
+            let (x, y) = chunk_pixel(pos, index);
+            *shade = pixel_shade(*material, x, y);
+        }
+    }
+}
+
+/// Shade a pixel gets when it's placed at (x, y). It only depends on the position,
+/// so regenerated chunks look the same; pixels that move carry their shade with them.
+pub fn pixel_shade(material: Material, x: i32, y: i32) -> u8 {
+    let y = if material.props().grain { y.div_euclid(GRAIN_LENGTH) } else { y };
+    let index = ((x as i64) << 32) | (y as u32 as i64);
+    (feature_hash(0, SHADE_SALT, index) >> 56) as u8
+}
+
+/// World pixel position of `index` within the chunk at `pos`
+fn chunk_pixel(pos: IVec2, index: usize) -> (i32, i32) {
+    (pos.x * CHUNK_SIZE + index as i32 % CHUNK_SIZE, pos.y * CHUNK_SIZE + index as i32 / CHUNK_SIZE)
+}
+
+/// Split a world pixel position into the chunk containing it and the index within that chunk
+pub fn chunk_coords(x: i32, y: i32) -> (IVec2, usize) {
+    let chunk = IVec2::new(x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE));
+    let local = (y.rem_euclid(CHUNK_SIZE) * CHUNK_SIZE + x.rem_euclid(CHUNK_SIZE)) as usize;
+    (chunk, local)
+}
+
+/// Anything pixels can be drawn into - the live world, or a single chunk being generated
+pub trait PixelCanvas {
+    fn set(&mut self, x: i32, y: i32, material: Material);
+
+    fn set_rect(&mut self, x: i32, y: i32, w: i32, h: i32, material: Material) {
+        for dy in 0..h {
+            for dx in 0..w {
+                self.set(x + dx, y + dy, material);
+            }
+        }
+    }
+
+    fn set_circle(&mut self, cx: i32, cy: i32, radius: i32, material: Material) {
+        let r_sq = radius * radius;
+        for y in (cy - radius)..=(cy + radius) {
+            for x in (cx - radius)..=(cx + radius) {
+                let dx = x - cx;
+                let dy = y - cy;
+                if dx * dx + dy * dy <= r_sq {
+                    self.set(x, y, material);
+                }
+            }
+        }
+    }
+}
+
+/// Draws into one chunk using world coordinates, ignoring anything outside it.
+/// Lets features that straddle chunk borders (like trees) be generated one chunk at a time.
+pub struct ChunkCanvas<'a> {
+    pub pos: IVec2,
+    pub chunk: &'a mut Chunk,
+}
+
+impl ChunkCanvas<'_> {
+    fn local_index(&self, x: i32, y: i32) -> Option<usize> {
+        let (pos, index) = chunk_coords(x, y);
+        (pos == self.pos).then_some(index)
+    }
+}
+
+impl PixelCanvas for ChunkCanvas<'_> {
+    fn set(&mut self, x: i32, y: i32, material: Material) {
+        if let Some(index) = self.local_index(x, y) {
+            self.chunk.pixels[index] = material;
+            self.chunk.lifetimes[index] = 0;
+            self.chunk.shades[index] = pixel_shade(material, x, y);
+        }
+    }
+}
+
+/// A chunk's pixels run-length encoded for saving to disk
+#[derive(Serialize, Deserialize)]
+pub struct EncodedChunk {
+    pub x: i32,
+    pub y: i32,
+    pub pixels: Vec<(Material, u32)>,
+    pub lifetimes: Vec<(u16, u32)>,
+    /// (pixel index, shade) for pixels whose shade isn't the one for where they are,
+    /// i.e. pixels that moved there
+    #[serde(default)]
+    pub shades: Vec<(u16, u8)>,
+}
+
+impl EncodedChunk {
+    pub fn encode(pos: IVec2, chunk: &Chunk) -> Self {
+        Self {
+            x: pos.x,
+            y: pos.y,
+            pixels: encode_runs(chunk.pixels.iter().copied()),
+            lifetimes: encode_runs(chunk.lifetimes.iter().copied()),
+            shades: chunk
+                .pixels
+                .iter()
+                .zip(&chunk.shades)
+                .enumerate()
+                .filter(|&(index, (material, shade))| {
+                    let (x, y) = chunk_pixel(pos, index);
+                    *shade != pixel_shade(*material, x, y)
+                })
+                .map(|(index, (_, shade))| (index as u16, *shade))
+                .collect(),
+        }
+    }
+
+    /// Rebuild the chunk, mapping the file's material ids through `remap`.
+    /// Decoded chunks count as modified since they no longer match the generator.
+    pub fn decode(&self, remap: impl Fn(Material) -> Material) -> Result<(IVec2, Chunk), String> {
+        let len = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+        let pos = IVec2::new(self.x, self.y);
+        let mut chunk = Chunk {
+            pixels: decode_runs(&self.pixels, len)?.into_iter().map(remap).collect(),
+            lifetimes: decode_runs(&self.lifetimes, len)?,
+            shades: vec![0; len],
+            light: vec![Light::default(); len],
+            modified: true,
+            awake: true,
+            dirty: true,
+            unlit: true,
+            outline_stale: true,
+        };
+        chunk.reset_shades(pos);
+        for &(index, shade) in &self.shades {
+            let slot = chunk
+                .shades
+                .get_mut(index as usize)
+                .ok_or_else(|| format!("shade for pixel {} is outside the chunk", index))?;
+            *slot = shade;
+        }
+        Ok((pos, chunk))
+    }
+}
+
+/// Collapse repeated values into (value, count) runs
+pub fn encode_runs<T: Copy + PartialEq>(values: impl Iterator<Item = T>) -> Vec<(T, u32)> {
+    let mut runs: Vec<(T, u32)> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some((last, count)) if *last == value => *count += 1,
+            _ => runs.push((value, 1)),
+        }
+    }
+    runs
+}
+
+/// Expand (value, count) runs, checking they cover exactly `len` values
+pub fn decode_runs<T: Copy>(runs: &[(T, u32)], len: usize) -> Result<Vec<T>, String> {
+    let mut values = Vec::with_capacity(len);
+    for &(value, count) in runs {
+        values.extend(std::iter::repeat_n(value, count as usize));
+    }
+    if values.len() != len {
+        return Err(format!("expected {} pixels but found {}", len, values.len()));
+    }
+    Ok(values)
+}
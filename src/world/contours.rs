@@ -0,0 +1,139 @@
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+/// Trace the outlines of the filled pixels in a `width` x `height` area with marching squares.
+/// Samples sit on pixel centres, so a filled pixel's outline runs along its edges with its
+/// outer corners cut off. Pixels that only touch diagonally get separate outlines, matching
+/// how chunks are flood filled. Returns closed loops in pixel coordinates, y down.
+pub fn trace_contours(width: i32, height: i32, filled: impl Fn(i32, i32) -> bool) -> Vec<Vec<Vec2>> {
+    let sample = |x: i32, y: i32| x >= 0 && y >= 0 && x < width && y < height && filled(x, y);
+
+    // Segment ends are edge midpoints, kept in doubled coordinates so they're whole numbers
+    // and segments meeting at a point join up exactly
+    let mut links: HashMap<IVec2, Vec<IVec2>> = HashMap::new();
+    let mut link = |a: IVec2, b: IVec2| {
+        links.entry(a).or_default().push(b);
+        links.entry(b).or_default().push(a);
+    };
+
+    // Cells run between sample points, one either side of the area so outlines close
+    for y in -1..height {
+        for x in -1..width {
+            let case = (sample(x, y) as u8) << 3
+                | (sample(x + 1, y) as u8) << 2
+                | (sample(x + 1, y + 1) as u8) << 1
+                | sample(x, y + 1) as u8;
+            let top = IVec2::new(2 * x + 1, 2 * y);
+            let right = IVec2::new(2 * x + 2, 2 * y + 1);
+            let bottom = IVec2::new(2 * x + 1, 2 * y + 2);
+            let left = IVec2::new(2 * x, 2 * y + 1);
+            match case {
+                1 | 14 => link(left, bottom),
+                2 | 13 => link(bottom, right),
+                3 | 12 => link(left, right),
+                4 | 11 => link(top, right),
+                6 | 9 => link(top, bottom),
+                7 | 8 => link(left, top),
+                5 => {
+                    link(top, right);
+                    link(left, bottom);
+                }
+                10 => {
+                    link(left, top);
+                    link(bottom, right);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Every point joins exactly two segments, so walking the links gives closed loops
+    let mut loops = Vec::new();
+    let mut starts: Vec<IVec2> = links.keys().copied().collect();
+    starts.sort_by_key(|p| (p.y, p.x));
+    for start in starts {
+        if !links.contains_key(&start) {
+            continue;
+        }
+        let mut points = Vec::new();
+        let mut previous = None;
+        let mut current = start;
+        while let Some(neighbours) = links.remove(&current) {
+            points.push(current.as_vec2() / 2.0);
+            let next = if previous == Some(neighbours[0]) { neighbours[1] } else { neighbours[0] };
+            previous = Some(current);
+            current = next;
+        }
+        if points.len() >= 3 {
+            loops.push(points);
+        }
+    }
+    loops
+}
+
+/// Simplify a closed loop with Douglas-Peucker, dropping points that are within `tolerance`
+/// of the line between the points kept either side of them
+pub fn simplify_loop(points: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    if points.len() < 4 {
+        return points.to_vec();
+    }
+
+    // Split the loop at the point furthest from the first, and simplify both halves as lines
+    let far = (1..points.len())
+        .max_by(|&a, &b| points[0].distance_squared(points[a]).total_cmp(&points[0].distance_squared(points[b])))
+        .unwrap();
+    let mut first: Vec<Vec2> = points[..=far].to_vec();
+    let mut second: Vec<Vec2> = points[far..].to_vec();
+    second.push(points[0]);
+    first = simplify_line(&first, tolerance);
+    second = simplify_line(&second, tolerance);
+
+    // Both halves end where the other starts
+    first.pop();
+    second.pop();
+    first.extend(second);
+
+    // Never flatten a loop into a line
+    if first.len() < 3 {
+        return points.to_vec();
+    }
+    first
+}
+
+/// Douglas-Peucker for an open line, always keeping both ends
+pub fn simplify_line(points: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    let mut spans = vec![(0, points.len() - 1)];
+    while let Some((start, end)) = spans.pop() {
+        let (a, b) = (points[start], points[end]);
+        let furthest = (start + 1..end)
+            .map(|i| (i, distance_to_segment(points[i], a, b)))
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((i, distance)) = furthest {
+            if distance > tolerance {
+                keep[i] = true;
+                spans.push((start, i));
+                spans.push((i, end));
+            }
+        }
+    }
+
+    points.iter().zip(keep).filter(|(_, keep)| *keep).map(|(p, _)| *p).collect()
+}
+
+/// Distance from `p` to the nearest point on the segment from `a` to `b`
+pub fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let t = if ab.length_squared() > 0.0 {
+        ((p - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    p.distance(a + ab * t)
+}
@@ -0,0 +1,141 @@
+use rand::Rng;
+use super::materials::Material;
+use super::simulation::{update_gas, PixelGrid};
+
+/// Lifetime range of a flame pixel in ticks
+pub const FLAME_LIFETIME: (u16, u16) = (8, 20);
+
+/// Lifetime range of a smoke pixel in ticks
+pub const SMOKE_LIFETIME: (u16, u16) = (60, 180);
+
+/// Chance per tick that an ember throws a spark particle
+pub const SPARK_CHANCE: f64 = 0.003;
+
+/// Chance per tick that an ember licks a new flame into the air next to it
+const FLAME_SPAWN_CHANCE: f64 = 0.3;
+
+/// Chance that a burnt-out ember leaves ash behind instead of nothing
+const ASH_CHANCE: f64 = 0.4;
+
+/// Chance that a dying flame leaves a puff of smoke
+const SMOKE_CHANCE: f64 = 0.3;
+
+/// Set a flammable pixel smouldering for its material's burn time
+/// Returns false if the pixel can't burn
+pub fn ignite(world: &mut impl PixelGrid, x: i32, y: i32) -> bool {
+    let material = world.get(x, y);
+    if !material.is_flammable() {
+        return false;
+    }
+    world.set_with_lifetime(x, y, Material::EMBER, material.burn_time());
+    true
+}
+
+/// Place a short-lived flame in an empty cell
+pub fn spawn_flame(world: &mut impl PixelGrid, rng: &mut impl Rng, x: i32, y: i32) {
+    if world.in_bounds(x, y) && world.get(x, y) == Material::AIR {
+        let lifetime = rng.gen_range(FLAME_LIFETIME.0..=FLAME_LIFETIME.1);
+        world.set_with_lifetime(x, y, Material::FIRE, lifetime);
+    }
+}
+
+fn spawn_smoke(world: &mut impl PixelGrid, rng: &mut impl Rng, x: i32, y: i32) {
+    let lifetime = rng.gen_range(SMOKE_LIFETIME.0..=SMOKE_LIFETIME.1);
+    world.set_with_lifetime(x, y, Material::SMOKE, lifetime);
+}
+
+/// Try to set flammable neighbours alight, each by its own flammability
+fn spread_fire(world: &mut impl PixelGrid, rng: &mut impl Rng, x: i32, y: i32) {
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            if dx == 0 && dy == 0 {
+                continue;
+            }
+            let neighbor = world.get(x + dx, y + dy);
+            if neighbor.is_flammable() && rng.gen_bool(neighbor.flammability()) {
+                ignite(world, x + dx, y + dy);
+            }
+        }
+    }
+}
+
+/// Water touching fire turns to steam and puts it out
+/// Returns true if the pixel was extinguished
+fn douse(world: &mut impl PixelGrid, rng: &mut impl Rng, x: i32, y: i32, remains: Material) -> bool {
+    for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+        if world.get(x + dx, y + dy) == Material::WATER {
+            spawn_smoke(world, rng, x + dx, y + dy);
+            world.set(x, y, remains);
+            return true;
+        }
+    }
+    false
+}
+
+/// Burning fuel: spreads to neighbours, throws flames and sparks, then burns out
+pub fn update_ember(
+    world: &mut impl PixelGrid,
+    rng: &mut impl Rng,
+    x: i32,
+    y: i32,
+    sparks: &mut Vec<(i32, i32)>,
+) {
+    if douse(world, rng, x, y, Material::ASH) {
+        return;
+    }
+
+    spread_fire(world, rng, x, y);
+
+    // Flames rise from the top and sides of burning material
+    if rng.gen_bool(FLAME_SPAWN_CHANCE) {
+        let flame_x = x + rng.gen_range(-1..=1);
+        spawn_flame(world, rng, flame_x, y - 1);
+    }
+
+    let lifetime = world.lifetime(x, y);
+    if lifetime <= 1 {
+        if rng.gen_bool(ASH_CHANCE) {
+            world.set(x, y, Material::ASH);
+        } else {
+            spawn_smoke(world, rng, x, y);
+        }
+        return;
+    }
+    world.set_lifetime(x, y, lifetime - 1);
+
+    if rng.gen_bool(SPARK_CHANCE) {
+        sparks.push((x, y));
+    }
+}
+
+/// Open flame: ignites what it touches and flickers upward before dying out
+pub fn update_flame(world: &mut impl PixelGrid, rng: &mut impl Rng, x: i32, y: i32) {
+    if douse(world, rng, x, y, Material::AIR) {
+        return;
+    }
+
+    spread_fire(world, rng, x, y);
+
+    let lifetime = world.lifetime(x, y);
+    if lifetime <= 1 {
+        if rng.gen_bool(SMOKE_CHANCE) {
+            spawn_smoke(world, rng, x, y);
+        } else {
+            world.set(x, y, Material::AIR);
+        }
+        return;
+    }
+    world.set_lifetime(x, y, lifetime - 1);
+    update_gas(world, rng, x, y);
+}
+
+/// Smoke drifts upward and thins out over time
+pub fn update_smoke(world: &mut impl PixelGrid, rng: &mut impl Rng, x: i32, y: i32) {
+    let lifetime = world.lifetime(x, y);
+    if lifetime <= 1 {
+        world.set(x, y, Material::AIR);
+        return;
+    }
+    world.set_lifetime(x, y, lifetime - 1);
+    update_gas(world, rng, x, y);
+}
@@ -1,99 +1,186 @@
+use bevy::platform::collections::HashSet;
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
-use super::{PixelWorld, Material};
+use super::chunk::CHUNK_SIZE;
+use super::contours::{simplify_line, simplify_loop, trace_contours};
+use super::materials::MaterialState;
+use super::{Material, PixelWorld, WorldService};
 
-#[derive(Component)]
-pub struct GroundCollider;
+/// How far (in pixels) a simplified ground outline may stray from the traced one
+const OUTLINE_TOLERANCE: f32 = 0.5;
 
-#[derive(Resource)]
-pub struct GroundColliderTimer {
-    pub timer: Timer,
+/// Collider following the ground in one world chunk
+#[derive(Component)]
+pub struct GroundCollider {
+    pub chunk: IVec2,
 }
 
-impl Default for GroundColliderTimer {
-    fn default() -> Self {
-        Self {
-            timer: Timer::from_seconds(0.5, TimerMode::Repeating),
+/// Rebuild the ground colliders of chunks whose ground changed, and drop the ones of
+/// chunks that were unloaded. Runs after everything that digs, places or breaks pixels
+/// and before Rapier steps, so bodies never fall through a gap dug this frame.
+pub fn update_ground_colliders(
+    mut commands: Commands,
+    mut world: ResMut<PixelWorld>,
+    colliders: Query<(Entity, &GroundCollider)>,
+) {
+    let stale: HashSet<IVec2> = world.take_stale_outlines().into_iter().collect();
+
+    for (entity, collider) in colliders.iter() {
+        if stale.contains(&collider.chunk) || !world.is_chunk_loaded(collider.chunk) {
+            commands.entity(entity).despawn();
         }
     }
+    for pos in &stale {
+        spawn_ground_collider(&mut commands, &world, *pos);
+    }
 }
 
-/// System to regenerate ground colliders based on current terrain
-pub fn update_ground_colliders(
-    mut commands: Commands,
-    world: Res<PixelWorld>,
-    existing_colliders: Query<Entity, With<GroundCollider>>,
-    time: Res<Time>,
-    mut timer: ResMut<GroundColliderTimer>,
-) {
-    // Only update every 0.5 seconds to avoid performance issues
-    timer.timer.tick(time.delta());
-    if !timer.timer.just_finished() {
+/// Spawn the polyline collider following every edge between ground and anything else in
+/// one chunk - surfaces, cave floors and ceilings, walls and overhangs alike. Chunks with
+/// no edges get none.
+fn spawn_ground_collider(commands: &mut Commands, world: &PixelWorld, pos: IVec2) {
+    let lines = chunk_outline(world, pos);
+    if lines.is_empty() {
         return;
     }
 
-    // Remove all existing ground colliders
-    let count = existing_colliders.iter().count();
-    for entity in existing_colliders.iter() {
-        commands.entity(entity).despawn();
+    let mut vertices: Vec<Vect> = Vec::new();
+    let mut indices: Vec<[u32; 2]> = Vec::new();
+    for line in lines {
+        let first = vertices.len() as u32;
+        indices.extend((first..first + line.len() as u32 - 1).map(|i| [i, i + 1]));
+        vertices.extend(line);
     }
+    commands.spawn((
+        Transform::from_xyz(0.0, 0.0, 0.0),
+        Collider::polyline(vertices, Some(indices)),
+        RigidBody::Fixed,
+        GroundCollider { chunk: pos },
+    ));
+}
 
-    info!("Removed {} existing ground colliders, regenerating...", count);
-
-    // Generate new colliders from the current terrain
-    // We'll scan for horizontal runs of solid ground (dirt/sand)
-    generate_ground_colliders(&mut commands, &world);
+/// Whether a material is part of the ground colliders follow. Powders like sand aren't:
+/// bodies push into them instead (`physics::pixel_displacement`).
+pub fn is_outlined(material: Material) -> bool {
+    let props = material.props();
+    props.ground && props.state == MaterialState::Solid
 }
 
-fn generate_ground_colliders(commands: &mut Commands, world: &PixelWorld) {
-    let width = world.width as i32;
-    let height = world.height as i32;
-
-    // For each column, find the topmost solid pixel (the surface)
-    let mut surface_points: Vec<Vec2> = vec![];
-
-    for x in (0..width).step_by(5) {  // Sample every 5 pixels for performance
-        // Scan from top to bottom to find first solid pixel
-        for y in 0..height {
-            if matches!(world.get(x, y), Material::Dirt | Material::Sand) {
-                // Convert pixel coordinates to world coordinates
-                let world_x = x as f32 - 400.0;
-                let world_y = 300.0 - y as f32;
-                surface_points.push(Vec2::new(world_x, world_y));
-                break;
+/// Outline of the ground in one chunk as lines in world coordinates, traced with marching
+/// squares and simplified. Each chunk owns the cells between its own pixel centres and its
+/// neighbours' to the right and below, so lines from neighbouring chunks meet exactly at
+/// the seams.
+fn chunk_outline(world: &PixelWorld, pos: IVec2) -> Vec<Vec<Vec2>> {
+    let origin = pos * CHUNK_SIZE;
+    let size = CHUNK_SIZE + 1;
+    let ground: Vec<bool> = world
+        .materials_in(IRect::from_corners(origin, origin + IVec2::splat(size)))
+        .iter()
+        .map(|material| is_outlined(*material))
+        .collect();
+    // All ground or no ground at all - nothing to outline
+    if ground.iter().all(|g| *g == ground[0]) {
+        return Vec::new();
+    }
+
+    // Only the cells this chunk owns; tracing also closes loops around the sampled area
+    let owned = |p: Vec2| p.x >= 0.0 && p.y >= 0.0 && p.x < CHUNK_SIZE as f32 && p.y < CHUNK_SIZE as f32;
+    let mut lines = Vec::new();
+    for outline in trace_contours(size, size, |x, y| ground[(y * size + x) as usize]) {
+        let count = outline.len();
+        let keep: Vec<bool> = (0..count).map(|i| owned((outline[i] + outline[(i + 1) % count]) / 2.0)).collect();
+        match keep.iter().position(|k| !k) {
+            // Loops entirely inside the chunk, like a small cave, stay closed
+            None => {
+                let mut line = simplify_loop(&outline, OUTLINE_TOLERANCE);
+                line.push(line[0]);
+                lines.push(line);
+            }
+            // Everything else is cut into runs at the chunk's edges. Simplifying keeps the
+            // ends, where they meet the next chunk's lines.
+            Some(gap) => {
+                let mut line = Vec::new();
+                for step in 1..=count {
+                    let i = (gap + step) % count;
+                    if keep[i] {
+                        if line.is_empty() {
+                            line.push(outline[i]);
+                        }
+                        line.push(outline[(i + 1) % count]);
+                    } else if !line.is_empty() {
+                        lines.push(simplify_line(&line, OUTLINE_TOLERANCE));
+                        line.clear();
+                    }
+                }
             }
         }
     }
 
-    // Create polyline colliders that precisely follow the terrain
-    // Split into segments to avoid single massive collider
-    let max_segment_length = 150;
-    let mut segment_count = 0;
-
-    for chunk_start in (0..surface_points.len()).step_by(max_segment_length) {
-        let chunk_end = (chunk_start + max_segment_length).min(surface_points.len());
+    // Outline points sit between pixel centres; pixel (x, y)'s centre is half a pixel in
+    // from the corner `pixel_to_world` gives
+    let corner = WorldService::pixel_to_world(origin.x, origin.y);
+    for line in &mut lines {
+        for point in line.iter_mut() {
+            *point = corner + Vec2::new(point.x + 0.5, -point.y - 0.5);
+        }
+    }
+    lines
+}
 
-        if chunk_end - chunk_start < 2 {
-            continue;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::platform::collections::HashMap;
+    use crate::world::chunk::Chunk;
+
+    /// Three by three chunks with dirt filling the bottom half
+    fn ground_world() -> PixelWorld {
+        let mut world = PixelWorld::new(3 * CHUNK_SIZE);
+        for cy in 0..3 {
+            for cx in 0..3 {
+                world.insert_chunk(IVec2::new(cx, cy), Chunk::default());
+            }
         }
+        for y in 48..3 * CHUNK_SIZE {
+            for x in 0..3 * CHUNK_SIZE {
+                world.set(x, y, Material::DIRT);
+            }
+        }
+        world
+    }
+
+    fn collider_entities(app: &mut App) -> HashMap<IVec2, Entity> {
+        let mut query = app.world_mut().query::<(Entity, &GroundCollider)>();
+        query.iter(app.world()).map(|(entity, collider)| (collider.chunk, entity)).collect()
+    }
 
-        let segment = &surface_points[chunk_start..chunk_end];
-
-        // Create a polyline collider from the points
-        let indices: Vec<[u32; 2]> = (0..segment.len() - 1)
-            .map(|i| [i as u32, (i + 1) as u32])
-            .collect();
-
-        if !indices.is_empty() {
-            commands.spawn((
-                Transform::from_xyz(0.0, 0.0, 0.0),
-                Collider::polyline(segment.to_vec(), Some(indices)),
-                RigidBody::Fixed,
-                GroundCollider,
-            ));
-            segment_count += 1;
+    #[test]
+    fn untouched_chunks_keep_their_colliders() {
+        let mut app = App::new();
+        app.insert_resource(ground_world()).add_systems(Update, update_ground_colliders);
+        app.update();
+        let before = collider_entities(&mut app);
+        assert!(before.contains_key(&IVec2::new(1, 1)));
+
+        app.world_mut().resource_mut::<PixelWorld>().set(40, 48, Material::AIR);
+        app.update();
+        let after = collider_entities(&mut app);
+
+        assert_eq!(before.len(), after.len());
+        assert_ne!(before[&IVec2::new(1, 1)], after[&IVec2::new(1, 1)]);
+        for (pos, entity) in before.iter().filter(|(pos, _)| **pos != IVec2::new(1, 1)) {
+            assert_eq!(after[pos], *entity, "chunk {pos} was rebuilt");
         }
     }
 
-    info!("Generated {} ground colliders with {} surface points", segment_count, surface_points.len());
+    #[test]
+    fn chunk_corner_marks_all_four_chunks_stale() {
+        let mut world = ground_world();
+        world.take_stale_outlines();
+
+        world.set(CHUNK_SIZE, CHUNK_SIZE * 2, Material::AIR);
+        let mut stale = world.take_stale_outlines();
+        stale.sort_by_key(|pos| (pos.y, pos.x));
+        assert_eq!(stale, vec![IVec2::new(0, 1), IVec2::new(1, 1), IVec2::new(0, 2), IVec2::new(1, 2)]);
+    }
 }
@@ -0,0 +1,383 @@
+use bevy::prelude::*;
+use bevy::platform::collections::HashSet;
+use bevy::tasks::{ComputeTaskPool, TaskPool};
+use std::collections::VecDeque;
+use std::f32::consts::TAU;
+use super::chunk::CHUNK_SIZE;
+use super::materials::{Material, MaterialState};
+use super::pixel_world::PixelWorld;
+
+/// Seconds for a full day and night
+const DAY_LENGTH: f32 = 600.0;
+
+/// Time of day a new game starts at - mid morning
+const START_TIME: f32 = 0.35;
+
+/// Sunlight at midnight, so open ground is still faintly visible by moonlight
+const NIGHT_SUN: f32 = 0.1;
+
+/// Steps the sun's strength is rounded to. Every loaded chunk is redrawn when it
+/// moves to the next step, so this trades smooth dawns against redraws.
+const SUN_STEPS: f32 = 40.0;
+
+/// Sunlight colour at night, at dawn and dusk, and at noon
+const MOONLIGHT: Vec3 = Vec3::new(0.55, 0.65, 1.0);
+const DUSK_LIGHT: Vec3 = Vec3::new(1.0, 0.6, 0.4);
+const NOON_LIGHT: Vec3 = Vec3::ONE;
+
+/// Background colour behind open air at noon, tinted and dimmed by the sunlight
+const DAY_SKY: Vec3 = Vec3::new(0.45, 0.65, 0.9);
+
+/// Colour of the dark rock behind caves and tunnels
+const CAVE_BACKDROP: Vec3 = Vec3::new(0.18, 0.13, 0.1);
+
+/// Light everything gets even when nothing lights it, so unlit rock isn't pure black
+const AMBIENT_LIGHT: f32 = 0.05;
+
+/// Brightest a light channel can be
+pub const MAX_LIGHT: u8 = 255;
+
+/// How far past its own chunk a chunk's lighting looks for light sources.
+/// Light must fade out within this distance in open air, or chunk edges would show seams.
+const LIGHT_MARGIN: i32 = CHUNK_SIZE / 2;
+const LIGHT_WINDOW: i32 = CHUNK_SIZE + 2 * LIGHT_MARGIN;
+
+/// Row of a chunk that the chunk below reads its incoming light from
+const HANDOFF_ROW: i32 = CHUNK_SIZE - LIGHT_MARGIN - 1;
+
+/// Seconds between lighting updates
+const LIGHT_INTERVAL: f32 = 0.05;
+
+/// Light reaching a pixel
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Light {
+    /// Light from the sky, scaled by the time of day when drawn
+    pub sky: u8,
+    /// Coloured light from glowing materials
+    pub rgb: [u8; 3],
+}
+
+impl Light {
+    const SKY: Light = Light { sky: MAX_LIGHT, rgb: [0; 3] };
+
+    /// The brighter of each channel
+    fn max(self, other: Light) -> Light {
+        Light {
+            sky: self.sky.max(other.sky),
+            rgb: [0, 1, 2].map(|i| self.rgb[i].max(other.rgb[i])),
+        }
+    }
+
+    /// Whether any channel is brighter than in `other`
+    fn brighter_than(&self, other: &Light) -> bool {
+        self.sky > other.sky || (0..3).any(|i| self.rgb[i] > other.rgb[i])
+    }
+}
+
+/// Clock driving the sun through day and night
+#[derive(Resource)]
+pub struct DayNightClock {
+    /// Time of day from 0 to 1: 0 is midnight, 0.5 is noon
+    pub time: f32,
+}
+
+impl Default for DayNightClock {
+    fn default() -> Self {
+        Self { time: START_TIME }
+    }
+}
+
+impl DayNightClock {
+    /// How far the sun is through the day: 0 at night, 1 when it's fully up
+    fn daylight(&self) -> f32 {
+        // -1 at midnight, 1 at noon; dawn and dusk are while it's near the horizon
+        let height = -(self.time * TAU).cos();
+        ((height + 0.2) / 0.5).clamp(0.0, 1.0)
+    }
+
+    /// Strength of sunlight from `NIGHT_SUN` to 1, rounded to `SUN_STEPS`
+    pub fn sun_strength(&self) -> f32 {
+        let strength = NIGHT_SUN + (1.0 - NIGHT_SUN) * self.daylight();
+        (strength * SUN_STEPS).round() / SUN_STEPS
+    }
+
+    /// Colour and strength of sunlight, reddening through dawn and dusk
+    pub fn sunlight(&self) -> Vec3 {
+        let day = (self.sun_strength() - NIGHT_SUN) / (1.0 - NIGHT_SUN);
+        let color = if day < 0.5 {
+            MOONLIGHT.lerp(DUSK_LIGHT, day * 2.0)
+        } else {
+            DUSK_LIGHT.lerp(NOON_LIGHT, day * 2.0 - 1.0)
+        };
+        color * self.sun_strength()
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.daylight() == 0.0
+    }
+
+    /// Hours and minutes on a 24 hour clock
+    pub fn hours_minutes(&self) -> (u32, u32) {
+        let minutes = (self.time * 24.0 * 60.0) as u32;
+        (minutes / 60 % 24, minutes % 60)
+    }
+}
+
+/// Lighting update timing, and the sunlight chunk textures were last drawn with
+#[derive(Resource)]
+pub struct LightingState {
+    timer: f32,
+    drawn_sunlight: Vec3,
+}
+
+impl Default for LightingState {
+    fn default() -> Self {
+        // Light the first chunks straight away instead of drawing them dark for a frame
+        Self {
+            timer: LIGHT_INTERVAL,
+            drawn_sunlight: Vec3::ZERO,
+        }
+    }
+}
+
+/// Move the clock on and colour the sky to match
+pub fn advance_clock(mut clock: ResMut<DayNightClock>, mut clear_color: ResMut<ClearColor>, time: Res<Time>) {
+    clock.time = (clock.time + time.delta_secs() / DAY_LENGTH).fract();
+    let sky = DAY_SKY * clock.sunlight();
+    clear_color.0 = Color::srgb(sky.x, sky.y, sky.z);
+}
+
+/// Work out lighting again for chunks that changed, and for everything their light reaches.
+/// Chunks take the light coming in from above from the chunk row above them, so rows are
+/// lit top to bottom; the chunks within a row are lit in parallel.
+pub fn update_lighting(
+    mut world: ResMut<PixelWorld>,
+    clock: Res<DayNightClock>,
+    mut state: ResMut<LightingState>,
+    time: Res<Time>,
+) {
+    // Sunlight only affects drawing, so a new sun step just redraws everything
+    let sunlight = clock.sunlight();
+    if sunlight != state.drawn_sunlight {
+        state.drawn_sunlight = sunlight;
+        world.mark_all_dirty();
+    }
+
+    state.timer += time.delta_secs();
+    if state.timer < LIGHT_INTERVAL {
+        return;
+    }
+    state.timer = 0.0;
+
+    // A chunk's window reaches half a chunk into each neighbour, so changes
+    // affect the lighting of every chunk around them
+    let mut stale = HashSet::new();
+    for pos in world.take_unlit_chunks() {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let neighbour = pos + IVec2::new(dx, dy);
+                if world.is_chunk_loaded(neighbour) {
+                    stale.insert(neighbour);
+                }
+            }
+        }
+    }
+
+    let rows = (world.height + CHUNK_SIZE - 1) / CHUNK_SIZE;
+    let pool = ComputeTaskPool::get_or_init(TaskPool::default);
+    for row in 0..rows {
+        let mut chunks: Vec<IVec2> = stale.iter().copied().filter(|pos| pos.y == row).collect();
+        if chunks.is_empty() {
+            continue;
+        }
+        chunks.sort_by_key(|pos| pos.x);
+
+        let lit = {
+            let world = &*world;
+            pool.scope(|scope| {
+                for &pos in &chunks {
+                    scope.spawn(async move { (pos, light_chunk(world, pos)) });
+                }
+            })
+        };
+
+        for (pos, light) in lit {
+            // When the light handed down to the next row changes, that row has to follow
+            let handoff = (HANDOFF_ROW * CHUNK_SIZE) as usize..((HANDOFF_ROW + 1) * CHUNK_SIZE) as usize;
+            let handoff_changed = world
+                .chunk(pos)
+                .is_some_and(|chunk| chunk.light[handoff.clone()] != light[handoff.clone()]);
+            if handoff_changed {
+                for dx in -1..=1 {
+                    let below = pos + IVec2::new(dx, 1);
+                    if world.is_chunk_loaded(below) {
+                        stale.insert(below);
+                    }
+                }
+            }
+            world.set_chunk_light(pos, light);
+        }
+    }
+}
+
+/// Light for every pixel of one chunk.
+/// Light is spread from glowing pixels and from the light arriving along the top of a
+/// window around the chunk, losing some for each pixel it passes through. Sky light
+/// falls straight down through open air without fading, so anything under open sky is
+/// fully lit and only spreads sideways into caves and overhangs.
+pub fn light_chunk(world: &PixelWorld, pos: IVec2) -> Vec<Light> {
+    let origin = pos * CHUNK_SIZE - IVec2::splat(LIGHT_MARGIN);
+    let len = (LIGHT_WINDOW * LIGHT_WINDOW) as usize;
+
+    let materials = world.materials_in(IRect::from_corners(origin, origin + IVec2::splat(LIGHT_WINDOW)));
+
+    let mut light = vec![Light::default(); len];
+    let mut queue = VecDeque::new();
+
+    // Glowing materials light themselves
+    for (index, material) in materials.iter().enumerate() {
+        if let Some(emission) = material.props().emission {
+            light[index].rgb = emission.map(|channel| (channel.clamp(0.0, 1.0) * MAX_LIGHT as f32) as u8);
+            queue.push_back(index);
+        }
+    }
+
+    // Light arriving from above: the chunk row above has already been lit, and the top of the world is open sky
+    for local_x in 0..LIGHT_WINDOW {
+        let (x, y) = (origin.x + local_x, origin.y - 1);
+        let incoming = if y < 0 { Light::SKY } else { world.light(x, y) };
+        let index = local_x as usize;
+        let entering = pass_through(incoming, materials[index], true);
+        if entering.brighter_than(&light[index]) {
+            light[index] = light[index].max(entering);
+            queue.push_back(index);
+        }
+    }
+
+    // Spread until nothing gets any brighter
+    while let Some(index) = queue.pop_front() {
+        let (local_x, local_y) = (index as i32 % LIGHT_WINDOW, index as i32 / LIGHT_WINDOW);
+        for (dx, dy) in [(0, 1), (-1, 0), (1, 0), (0, -1)] {
+            let (nx, ny) = (local_x + dx, local_y + dy);
+            if !(0..LIGHT_WINDOW).contains(&nx) || !(0..LIGHT_WINDOW).contains(&ny) {
+                continue;
+            }
+            let neighbour = (ny * LIGHT_WINDOW + nx) as usize;
+            let spread = pass_through(light[index], materials[neighbour], dy == 1);
+            if spread.brighter_than(&light[neighbour]) {
+                light[neighbour] = light[neighbour].max(spread);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    // Keep just the chunk itself
+    let mut chunk_light = Vec::with_capacity((CHUNK_SIZE * CHUNK_SIZE) as usize);
+    for local_y in LIGHT_MARGIN..LIGHT_MARGIN + CHUNK_SIZE {
+        let start = (local_y * LIGHT_WINDOW + LIGHT_MARGIN) as usize;
+        chunk_light.extend_from_slice(&light[start..start + CHUNK_SIZE as usize]);
+    }
+    chunk_light
+}
+
+/// Light left after passing into a pixel of `material`
+fn pass_through(light: Light, material: Material, downwards: bool) -> Light {
+    let props = material.props();
+    let falloff = props.light_falloff();
+    let open = matches!(props.state, MaterialState::Empty | MaterialState::Gas);
+    Light {
+        // Sunlight shines straight down through open air, so whatever made it through
+        // a tree's leaves still reaches the ground
+        sky: if downwards && open { light.sky } else { light.sky.saturating_sub(falloff) },
+        rgb: light.rgb.map(|channel| channel.saturating_sub(falloff)),
+    }
+}
+
+/// Brightness of a light channel from 0 to 1. Light fades out in a straight line,
+/// which looks like it drops off too quickly, so it's drawn on a curve.
+fn brightness(channel: u8) -> f32 {
+    (channel as f32 / MAX_LIGHT as f32).sqrt()
+}
+
+/// How bright (x, y) looks right now, from 0 to 1
+pub fn light_level(world: &PixelWorld, clock: &DayNightClock, x: i32, y: i32) -> f32 {
+    let light = world.light(x, y);
+    let sky = brightness(light.sky) * clock.sun_strength();
+    let glow = brightness(light.rgb.iter().copied().max().unwrap_or(0));
+    sky.max(glow)
+}
+
+/// Final colour of a pixel with its lighting applied.
+/// Open air stays see-through under the sky so the sky colour shows, and turns into
+/// dark cave wall where the sky can't reach.
+pub fn lit_color(color: [u8; 4], light: Light, sunlight: Vec3, empty: bool) -> [u8; 4] {
+    let sky = brightness(light.sky);
+    let glow = Vec3::from_array(light.rgb.map(brightness));
+    if empty {
+        let backdrop = CAVE_BACKDROP * (glow + Vec3::splat(AMBIENT_LIGHT * 4.0)).min(Vec3::ONE);
+        let [r, g, b] = backdrop.to_array().map(|channel| (channel * 255.0) as u8);
+        return [r, g, b, ((1.0 - sky) * 255.0) as u8];
+    }
+    let total = (sunlight * sky + glow + Vec3::splat(AMBIENT_LIGHT)).min(Vec3::ONE);
+    let [r, g, b] = [0, 1, 2].map(|i| (color[i] as f32 * total[i]) as u8);
+    [r, g, b, color[3]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::pixel_world::test_support::{empty_world, fill};
+
+    /// Three chunks across with the top four rows of chunks all `material`
+    fn world_of(material: Material) -> PixelWorld {
+        let mut world = empty_world(3);
+        fill(&mut world, IRect::new(0, 0, 3 * CHUNK_SIZE, 4 * CHUNK_SIZE), material);
+        world
+    }
+
+    /// Light the chunk rows top to bottom, like `update_lighting`
+    fn light_rows(world: &mut PixelWorld, rows: i32) {
+        for row in 0..rows {
+            for cx in 0..3 {
+                let pos = IVec2::new(cx, row);
+                let light = light_chunk(world, pos);
+                world.set_chunk_light(pos, light);
+            }
+        }
+    }
+
+    #[test]
+    fn sky_light_fills_open_air() {
+        let mut world = world_of(Material::AIR);
+        light_rows(&mut world, 2);
+        for (x, y) in [(40, 0), (33, 31), (62, 63), (48, 40)] {
+            assert_eq!(world.light(x, y).sky, MAX_LIGHT, "at ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn light_fades_out_underground() {
+        let mut world = world_of(Material::AIR);
+        fill(&mut world, IRect::new(0, 16, 3 * CHUNK_SIZE, 4 * CHUNK_SIZE), Material::DIRT);
+        light_rows(&mut world, 3);
+
+        let depths: Vec<u8> = (16..64).map(|y| world.light(48, y).sky).collect();
+        assert!(depths[0] > 0 && depths[0] < MAX_LIGHT);
+        assert!(depths.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert_eq!(depths[20], 0);
+    }
+
+    #[test]
+    fn glowing_fungus_lights_a_dark_cave() {
+        let mut world = world_of(Material::STONE);
+        fill(&mut world, IRect::new(36, 68, 60, 92), Material::AIR);
+        world.set(48, 91, Material::GLOW_FUNGUS);
+        light_rows(&mut world, 3);
+
+        let near = world.light(48, 86);
+        assert_eq!(near.sky, 0);
+        assert!(near.rgb[1] > 0);
+        // Rock well away from the cave stays dark
+        assert_eq!(world.light(10, 80), Light::default());
+    }
+}
@@ -1,39 +1,395 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use super::particles::MaterialInteractionParams;
+use crate::tools::components::Tool;
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub enum Material {
-    Air,
-    Dirt,
-    Wood,
-    Sand,
-    Leaf,
-    Fiber, // Bush fiber for making rope
-}
+/// Compact material id stored in every pixel.
+/// All properties live in the `MaterialRegistry`; the constants below are the
+/// built-in materials the simulation refers to by name.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct Material(pub u8);
 
 impl Material {
+    pub const AIR: Material = Material(0);
+    pub const DIRT: Material = Material(1);
+    pub const WOOD: Material = Material(2);
+    pub const SAND: Material = Material(3);
+    pub const LEAF: Material = Material(4);
+    pub const FIBER: Material = Material(5); // Bush fiber for making rope
+    pub const WATER: Material = Material(6);
+    #[allow(dead_code)] // Only ever made through Sand's wet_variant
+    pub const WET_SAND: Material = Material(7); // Sand that has soaked up water
+    pub const FIRE: Material = Material(8); // Short-lived flame in open air
+    pub const EMBER: Material = Material(9); // Burning fuel - spreads fire until it burns out
+    pub const SMOKE: Material = Material(10);
+    pub const ASH: Material = Material(11);
+    pub const CLAY: Material = Material(12);
+    pub const STONE: Material = Material(13);
+    pub const IRON_ORE: Material = Material(14);
+    pub const GLOW_FUNGUS: Material = Material(15); // Grows on cave floors and lights them up
+    pub const ROOT: Material = Material(16); // Holds up whatever grows from it, even over open water
+    pub const SANDSTONE: Material = Material(17); // Crumbles into sand once undermined
+
+    /// Names of the built-in materials, indexed by id
+    const BUILTIN_NAMES: [&'static str; 18] = [
+        "Air", "Dirt", "Wood", "Sand", "Leaf", "Fiber",
+        "Water", "WetSand", "Fire", "Ember", "Smoke", "Ash",
+        "Clay", "Stone", "IronOre", "GlowFungus", "Root", "Sandstone",
+    ];
+
+    /// Properties of this material from the global registry
+    pub fn props(&self) -> &'static MaterialProperties {
+        MaterialRegistry::global().get(*self)
+    }
+
     pub fn color(&self) -> Color {
-        match self {
-            Material::Air => Color::srgba(0.1, 0.1, 0.15, 0.0),
-            Material::Dirt => Color::srgb(0.4, 0.3, 0.2),
-            Material::Wood => Color::srgb(0.5, 0.3, 0.15),
-            Material::Sand => Color::srgb(0.8, 0.7, 0.5),
-            Material::Leaf => Color::srgb(0.2, 0.7, 0.3), // Vibrant green
-            Material::Fiber => Color::srgb(0.6, 0.7, 0.4), // Brownish-green fibrous plant
-        }
+        self.props().base_color()
     }
 
     pub fn is_solid(&self) -> bool {
-        !matches!(self, Material::Air)
+        matches!(self.props().state, MaterialState::Solid | MaterialState::Powder)
+    }
+
+    /// Liquids flow sideways and level out instead of piling up
+    pub fn is_liquid(&self) -> bool {
+        self.props().state == MaterialState::Liquid
+    }
+
+    /// Powders fall and slide diagonally into piles
+    pub fn is_powder(&self) -> bool {
+        self.props().state == MaterialState::Powder
+    }
+
+    /// Gases rise and drift until they dissipate
+    pub fn is_gas(&self) -> bool {
+        self.props().state == MaterialState::Gas
     }
 
     pub fn density(&self) -> u8 {
-        match self {
-            Material::Air => 0,
-            Material::Leaf => 1, // Very light
-            Material::Fiber => 2, // Light but denser than leaves
-            Material::Sand => 2,
-            Material::Dirt => 3,
-            Material::Wood => 5,
+        self.props().density
+    }
+
+    /// Chance per tick to catch fire while touching fire or embers
+    pub fn flammability(&self) -> f64 {
+        self.props().flammability
+    }
+
+    /// How many simulation ticks (60/s) this material smoulders as an ember
+    pub fn burn_time(&self) -> u16 {
+        self.props().burn_time
+    }
+
+    pub fn is_flammable(&self) -> bool {
+        self.flammability() > 0.0
+    }
+}
+
+/// How a material moves in the pixel simulation
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaterialState {
+    /// Empty space that anything can move into
+    Empty,
+    /// Static - never moves on its own
+    Solid,
+    /// Falls and piles up
+    Powder,
+    /// Falls and flows sideways to level out
+    Liquid,
+    /// Rises and drifts
+    Gas,
+}
+
+/// What a solid does when nothing holds it up any more
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unsupported {
+    /// Stays where it is, and holds up anything attached to it
+    #[default]
+    Stays,
+    /// Breaks off along with whatever it's attached to as a rigid pixel body
+    Falls,
+    /// Falls apart into loose pixels of its `crumbles_into` material
+    Crumbles,
+}
+
+fn default_hardness() -> f64 {
+    0.3
+}
+
+fn default_fluid_density() -> f32 {
+    1.0
+}
+
+fn default_slide_chance() -> f64 {
+    1.0
+}
+
+/// Everything the game knows about one material, as defined in the materials file
+#[derive(Clone, Debug, Deserialize)]
+pub struct MaterialProperties {
+    pub name: String,
+    pub state: MaterialState,
+    /// Colour variations as sRGB RGBA; the first entry is the base colour.
+    /// Each pixel shows one of them, picked by its shade.
+    pub palette: Vec<[f32; 4]>,
+    /// Shades run in short vertical streaks instead of varying every pixel, like wood grain
+    #[serde(default)]
+    pub grain: bool,
+    /// Colour of the light this material gives off as sRGB RGB, if it glows
+    #[serde(default)]
+    pub emission: Option<[f32; 3]>,
+    /// Light lost per pixel passing through, out of 255. Defaults by state.
+    #[serde(default)]
+    pub light_falloff: Option<u8>,
+    /// Heavier materials sink through lighter liquids and powders
+    pub density: u8,
+    /// Weight compared to water, for rigid bodies floating in liquids. Bodies lighter than
+    /// the liquid they're in float, heavier ones sink.
+    #[serde(default = "default_fluid_density")]
+    pub fluid_density: f32,
+    /// Chance that a tool hit fails to break a pixel
+    #[serde(default = "default_hardness")]
+    pub hardness: f64,
+    /// Chance per tick to catch fire while touching fire or embers
+    #[serde(default)]
+    pub flammability: f64,
+    /// Ticks spent smouldering as an ember once ignited
+    #[serde(default)]
+    pub burn_time: u16,
+    /// Terrain the player walks on, which gets ground colliders
+    #[serde(default)]
+    pub ground: bool,
+    /// What happens to this material once it's no longer connected to the bottom of the world
+    #[serde(default)]
+    pub unsupported: Unsupported,
+    /// Loose material a solid turns into when it crumbles
+    #[serde(default)]
+    pub crumbles_into: Option<String>,
+    /// Weight (summed density) a pixel can hold up resting on what's below it before it's
+    /// crushed. Never crushed if unset.
+    #[serde(default)]
+    pub compressive_strength: Option<f32>,
+    /// Weight a pixel can hold up when it hangs off its neighbours to the side or above
+    /// before it snaps. Never snaps if unset.
+    #[serde(default)]
+    pub tensile_strength: Option<f32>,
+    /// How hard (deceleration in pixels per second squared) a rigid body can hit something
+    /// before it cracks where this material touches it. Never cracks if unset.
+    #[serde(default)]
+    pub impact_strength: Option<f32>,
+    /// Material a rigid body's pixels of this turn into when the body settles back into the
+    /// world, so fallen trees become ground
+    #[serde(default)]
+    pub settles_into: Option<String>,
+    /// Chance per tick that a settled powder slides diagonally
+    #[serde(default = "default_slide_chance")]
+    pub slide_chance: f64,
+    /// Material this turns into when it soaks up a liquid
+    #[serde(default)]
+    pub wet_variant: Option<String>,
+    /// Tools that can break this material
+    #[serde(default)]
+    pub breakable_by: Vec<Tool>,
+    /// Particles spawned when this material is broken
+    #[serde(default)]
+    pub particles: MaterialInteractionParams,
+}
+
+impl MaterialProperties {
+    /// Chance that a single tool hit breaks a pixel of this material
+    pub fn break_chance(&self) -> f64 {
+        (1.0 - self.hardness).clamp(0.0, 1.0)
+    }
+
+    /// Light lost per pixel passing through: light fades out over half a chunk of
+    /// open air and within a few pixels of solid ground
+    pub fn light_falloff(&self) -> u8 {
+        self.light_falloff.unwrap_or(match self.state {
+            MaterialState::Empty | MaterialState::Gas => 16,
+            MaterialState::Liquid => 20,
+            MaterialState::Solid | MaterialState::Powder => 24,
+        })
+    }
+
+    pub fn base_color(&self) -> Color {
+        self.shade_color(0)
+    }
+
+    /// Palette entry shown for a pixel with the given shade
+    pub fn shade_color(&self, shade: u8) -> Color {
+        let [r, g, b, a] = match self.palette.len() {
+            0 => [1.0, 0.0, 1.0, 1.0],
+            len => self.palette[shade as usize % len],
+        };
+        Color::srgba(r, g, b, a)
+    }
+}
+
+#[derive(Deserialize)]
+struct MaterialFile {
+    materials: Vec<MaterialProperties>,
+}
+
+/// Data-driven table of material properties, loaded from `assets/materials.json`.
+/// There is one registry, `MaterialRegistry::global()`; it isn't a Bevy resource.
+pub struct MaterialRegistry {
+    materials: Vec<MaterialProperties>,
+    by_name: HashMap<String, Material>,
+    /// Resolved `wet_variant` for each material
+    wet_variants: Vec<Option<Material>>,
+    /// Resolved `crumbles_into` for each material
+    crumble_variants: Vec<Option<Material>>,
+    /// Resolved `settles_into` for each material
+    settle_variants: Vec<Option<Material>>,
+    /// Whether each material is one that bodies settle into
+    settled: Vec<bool>,
+}
+
+static REGISTRY: OnceLock<MaterialRegistry> = OnceLock::new();
+
+impl MaterialRegistry {
+    const MATERIALS_FILE: &'static str = "assets/materials.json";
+    const DEFAULT_MATERIALS: &'static str = include_str!("../../assets/materials.json");
+
+    /// The registry used by `Material`'s property helpers, loaded on first use
+    pub fn global() -> &'static MaterialRegistry {
+        REGISTRY.get_or_init(Self::load)
+    }
+
+    /// Load materials from file, falling back to the built-in defaults if it's missing or invalid
+    pub fn load() -> Self {
+        if Path::new(Self::MATERIALS_FILE).exists() {
+            match fs::read_to_string(Self::MATERIALS_FILE)
+                .map_err(|e| e.to_string())
+                .and_then(|contents| Self::from_json(&contents))
+            {
+                Ok(registry) => return registry,
+                Err(e) => warn!("Failed to load {}: {}, using defaults", Self::MATERIALS_FILE, e),
+            }
+        }
+        Self::from_json(Self::DEFAULT_MATERIALS).expect("built-in materials file is valid")
+    }
+
+    /// Build a registry from a materials file.
+    /// Built-in materials keep their fixed ids; any others are numbered after them in file order.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let file: MaterialFile = serde_json::from_str(json).map_err(|e| e.to_string())?;
+
+        let mut slots: Vec<Option<MaterialProperties>> = vec![None; Material::BUILTIN_NAMES.len()];
+        for props in file.materials {
+            match Material::BUILTIN_NAMES.iter().position(|name| *name == props.name) {
+                Some(id) => slots[id] = Some(props),
+                None => slots.push(Some(props)),
+            }
         }
+
+        if slots.len() > u8::MAX as usize + 1 {
+            return Err(format!("too many materials ({}), at most 256 are supported", slots.len()));
+        }
+
+        let materials = slots
+            .into_iter()
+            .enumerate()
+            .map(|(id, props)| {
+                props.ok_or_else(|| format!("missing built-in material '{}'", Material::BUILTIN_NAMES[id]))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let by_name: HashMap<String, Material> = materials
+            .iter()
+            .enumerate()
+            .map(|(id, props)| (props.name.clone(), Material(id as u8)))
+            .collect();
+
+        let wet_variants = materials
+            .iter()
+            .map(|props| match &props.wet_variant {
+                Some(name) => by_name
+                    .get(name)
+                    .copied()
+                    .map(Some)
+                    .ok_or_else(|| format!("'{}' has unknown wet_variant '{}'", props.name, name)),
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let crumble_variants = materials
+            .iter()
+            .map(|props| match &props.crumbles_into {
+                Some(name) => by_name
+                    .get(name)
+                    .copied()
+                    .map(Some)
+                    .ok_or_else(|| format!("'{}' has unknown crumbles_into '{}'", props.name, name)),
+                // Powders are loose already; solids need something to crumble into
+                None if props.unsupported == Unsupported::Crumbles && props.state == MaterialState::Solid => {
+                    Err(format!("'{}' crumbles but has no crumbles_into", props.name))
+                }
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let settle_variants = materials
+            .iter()
+            .map(|props| match &props.settles_into {
+                Some(name) => by_name
+                    .get(name)
+                    .copied()
+                    .map(Some)
+                    .ok_or_else(|| format!("'{}' has unknown settles_into '{}'", props.name, name)),
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut settled = vec![false; materials.len()];
+        for variant in settle_variants.iter().flatten() {
+            settled[variant.0 as usize] = true;
+        }
+
+        Ok(Self { materials, by_name, wet_variants, crumble_variants, settle_variants, settled })
+    }
+
+    pub fn get(&self, material: Material) -> &MaterialProperties {
+        // Unknown ids (e.g. from an old save) behave like air
+        self.materials
+            .get(material.0 as usize)
+            .unwrap_or(&self.materials[Material::AIR.0 as usize])
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<Material> {
+        self.by_name.get(name).copied()
+    }
+
+    /// What a material becomes when it soaks up liquid, if anything
+    pub fn wet_variant(&self, material: Material) -> Option<Material> {
+        self.wet_variants.get(material.0 as usize).copied().flatten()
+    }
+
+    /// Loose material this one turns into when it crumbles, if it isn't loose already
+    pub fn crumbles_into(&self, material: Material) -> Option<Material> {
+        self.crumble_variants.get(material.0 as usize).copied().flatten()
+    }
+
+    /// What a body's pixel of this material becomes when the body settles into the world
+    pub fn settles_into(&self, material: Material) -> Material {
+        self.settle_variants.get(material.0 as usize).copied().flatten().unwrap_or(material)
+    }
+
+    /// Whether bodies settle into this material, like fallen logs
+    pub fn is_settled(&self, material: Material) -> bool {
+        self.settled.get(material.0 as usize).copied().unwrap_or(false)
+    }
+
+    /// All registered materials in id order
+    pub fn iter(&self) -> impl Iterator<Item = (Material, &MaterialProperties)> {
+        self.materials
+            .iter()
+            .enumerate()
+            .map(|(id, props)| (Material(id as u8), props))
     }
 }
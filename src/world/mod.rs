@@ -1,36 +1,52 @@
 pub mod pixel_world;
+pub mod chunk;
+pub mod streaming;
 pub mod materials;
 pub mod terrain;
-pub mod digging;
+pub mod biomes;
+pub mod vegetation;
 pub mod ground_colliders;
 pub mod particles;
 pub mod service;
+pub mod fire;
+pub mod simulation;
+pub mod lighting;
+pub mod contours;
 
 pub use pixel_world::PixelWorld;
-pub use materials::Material;
+pub use materials::{Material, MaterialRegistry};
 pub use particles::ParticleSpawnEvent;
 pub use service::WorldService;
+pub use terrain::{TerrainGenerator, WorldSeed};
+pub use streaming::ChunkStore;
 
 use bevy::prelude::*;
-use crate::constants::{WORLD_PIXEL_WIDTH, WORLD_PIXEL_HEIGHT};
+use bevy_rapier2d::prelude::PhysicsSet;
+use crate::constants::WORLD_PIXEL_HEIGHT;
 
 pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
+        let seed = WorldSeed::from_args();
         app
-            .insert_resource(PixelWorld::new(WORLD_PIXEL_WIDTH, WORLD_PIXEL_HEIGHT))
+            .insert_resource(ChunkStore::for_seed(seed.0))
+            .insert_resource(seed)
+            .insert_resource(PixelWorld::new(WORLD_PIXEL_HEIGHT))
             .insert_resource(WorldService)
-            .init_resource::<ground_colliders::GroundColliderTimer>()
+            .init_resource::<simulation::PixelSimulation>()
+            .init_resource::<lighting::DayNightClock>()
+            .init_resource::<lighting::LightingState>()
             .add_plugins(particles::ParticlePlugin)
-            .add_systems(Startup, (
-                pixel_world::setup_renderer,
-                terrain::setup_terrain,
-            ))
+            .add_systems(Startup, streaming::setup_world)
             .add_systems(Update, (
-                pixel_world::update_pixels,
+                streaming::stream_chunks,
+                simulation::update_pixels,
+                lighting::advance_clock,
+                lighting::update_lighting,
                 pixel_world::render_pixels,
-                ground_colliders::update_ground_colliders,
-            ).chain());
+            ).chain())
+            // After every system that changes pixels, so Rapier sees this frame's ground
+            .add_systems(PostUpdate, ground_colliders::update_ground_colliders.before(PhysicsSet::SyncBackend));
     }
 }
@@ -1,5 +1,6 @@
 use bevy::prelude::*;
 use rand::Rng;
+use serde::Deserialize;
 use super::materials::Material;
 
 pub struct ParticlePlugin;
@@ -32,7 +33,8 @@ pub struct Particle {
 }
 
 /// Parameters for spawning particles when interacting with materials
-#[derive(Clone)]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
 pub struct MaterialInteractionParams {
     /// Number of particles to spawn per interaction
     pub particle_count_range: (u32, u32),
@@ -61,63 +63,14 @@ impl Default for MaterialInteractionParams {
     }
 }
 
-impl MaterialInteractionParams {
-    /// Get interaction parameters based on material type
-    pub fn for_material(material: Material) -> Self {
-        match material {
-            Material::Dirt => Self {
-                particle_count_range: (2, 5), // Reduced from (5, 12)
-                speed_range: (20.0, 60.0),
-                spread: 0.9,
-                lifetime_range: (0.3, 0.6), // Reduced from (0.4, 1.0)
-                size_range: (1.5, 3.0), // Reduced max from 3.5
-                gravity: 200.0,
-            },
-            Material::Wood => Self {
-                particle_count_range: (2, 4), // Reduced from (3, 8)
-                speed_range: (40.0, 100.0),
-                spread: 0.7,
-                lifetime_range: (0.3, 0.7), // Reduced from (0.5, 1.2)
-                size_range: (1.0, 2.0), // Reduced max from 2.5
-                gravity: 120.0,
-            },
-            Material::Sand => Self {
-                particle_count_range: (3, 6), // Reduced from (8, 15)
-                speed_range: (15.0, 50.0),
-                spread: 1.0,
-                lifetime_range: (0.2, 0.5), // Reduced from (0.3, 0.7)
-                size_range: (0.8, 1.5), // Reduced max from 2.0
-                gravity: 250.0,
-            },
-            Material::Leaf => Self {
-                particle_count_range: (3, 7),
-                speed_range: (10.0, 40.0),
-                spread: 1.0,
-                lifetime_range: (0.4, 0.8),
-                size_range: (1.0, 2.5),
-                gravity: 80.0, // Very light, floats more
-            },
-            Material::Fiber => Self {
-                particle_count_range: (4, 8),
-                speed_range: (15.0, 45.0),
-                spread: 0.8,
-                lifetime_range: (0.3, 0.7),
-                size_range: (1.0, 2.0),
-                gravity: 100.0, // Light but heavier than leaves
-            },
-            Material::Air => Self::default(),
-        }
-    }
-}
-
 /// System that handles particle spawn events
 fn handle_particle_spawn_events(
     mut commands: Commands,
     mut events: EventReader<ParticleSpawnEvent>,
 ) {
     for event in events.read() {
-        let params = MaterialInteractionParams::for_material(event.material);
-        spawn_material_particles(&mut commands, event.position, event.material, &params);
+        let params = &event.material.props().particles;
+        spawn_material_particles(&mut commands, event.position, event.material, params);
     }
 }
 
@@ -128,7 +81,7 @@ pub fn spawn_material_particles(
     material: Material,
     params: &MaterialInteractionParams,
 ) {
-    if material == Material::Air {
+    if material == Material::AIR {
         return;
     }
 
@@ -1,191 +1,494 @@
 use bevy::prelude::*;
-use rand::Rng;
-use super::materials::Material;
+use bevy::platform::collections::HashMap;
+use super::chunk::{chunk_coords, pixel_shade, Chunk, PixelCanvas, CHUNK_SIZE};
+use super::ground_colliders::is_outlined;
+use super::lighting::{lit_color, DayNightClock, Light};
+use super::materials::{Material, MaterialRegistry, MaterialState, Unsupported};
+use super::service::WorldService;
 
+/// The pixel grid, stored as chunks that are streamed in and out around the camera.
+/// Horizontally the world is unbounded; vertically it spans rows `0..height`.
 #[derive(Resource)]
 pub struct PixelWorld {
-    pub width: usize,
-    pub height: usize,
-    pub pixels: Vec<Material>,
-    dirty_chunks: Vec<bool>,
-    chunk_size: usize,
-    update_timer: f32,
+    pub height: i32,
+    chunks: HashMap<IVec2, Chunk>,
+    pub(super) update_timer: f32,
+    /// Simulation ticks so far, so each tick gets its own random stream
+    pub(super) tick: u64,
+    /// Pixels that held something up and were removed since the last support check
+    support_lost: Vec<(i32, i32)>,
+    /// Pixels where a solid was added or removed since loads were last worked out
+    load_changed: Vec<(i32, i32)>,
+    /// Pixels covered by rigid bodies this frame, per chunk, so loose pixels rest on them
+    body_cover: HashMap<IVec2, Vec<bool>>,
 }
 
 impl PixelWorld {
-    pub fn new(width: usize, height: usize) -> Self {
-        let chunk_size = 32;
-        let num_chunks = (width / chunk_size) * (height / chunk_size);
-
+    pub fn new(height: i32) -> Self {
         Self {
-            width,
             height,
-            pixels: vec![Material::Air; width * height],
-            dirty_chunks: vec![true; num_chunks],
-            chunk_size,
+            chunks: HashMap::new(),
             update_timer: 0.0,
+            tick: 0,
+            support_lost: Vec::new(),
+            load_changed: Vec::new(),
+            body_cover: HashMap::new(),
         }
     }
 
+    /// Simulation ticks so far
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Whether (x, y) is inside a loaded chunk
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        y >= 0 && y < self.height && self.chunks.contains_key(&chunk_coords(x, y).0)
+    }
+
+    /// Inside the world's rows but in a chunk that isn't loaded right now
+    pub fn is_unloaded(&self, x: i32, y: i32) -> bool {
+        y >= 0 && y < self.height && !self.chunks.contains_key(&chunk_coords(x, y).0)
+    }
+
+    /// Material at (x, y); air outside the world or in unloaded chunks
     pub fn get(&self, x: i32, y: i32) -> Material {
-        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
-            return Material::Air;
-        }
-        self.pixels[y as usize * self.width + x as usize]
+        let (pos, index) = chunk_coords(x, y);
+        self.chunks.get(&pos).map_or(Material::AIR, |chunk| chunk.pixels[index])
     }
 
     pub fn set(&mut self, x: i32, y: i32, material: Material) {
-        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
-            return;
+        let (pos, index) = chunk_coords(x, y);
+        if let Some(chunk) = self.chunks.get_mut(&pos) {
+            let old = std::mem::replace(&mut chunk.pixels[index], material);
+            chunk.lifetimes[index] = 0;
+            chunk.shades[index] = pixel_shade(material, x, y);
+            chunk.modified = true;
+            chunk.dirty = true;
+            chunk.unlit = true;
+            self.wake(pos, index);
+
+            if old != material {
+                // Whatever rested on a solid or powder that's gone, or on something that
+                // stayed put turning into something that doesn't, may have lost its support
+                if old.is_solid() && (!material.is_solid() || old.props().unsupported == Unsupported::Stays) {
+                    self.support_lost.push((x, y));
+                }
+                if old.props().state == MaterialState::Solid || material.props().state == MaterialState::Solid {
+                    self.load_changed.push((x, y));
+                }
+                if is_outlined(old) || is_outlined(material) {
+                    self.mark_outline_stale(pos, index);
+                }
+            }
         }
-        let idx = y as usize * self.width + x as usize;
-        self.pixels[idx] = material;
-        self.mark_chunk_dirty(x, y);
     }
 
-    fn mark_chunk_dirty(&mut self, x: i32, y: i32) {
-        let chunk_x = x as usize / self.chunk_size;
-        let chunk_y = y as usize / self.chunk_size;
-        let chunks_wide = self.width / self.chunk_size;
-        let chunk_idx = chunk_y * chunks_wide + chunk_x;
-        if chunk_idx < self.dirty_chunks.len() {
-            self.dirty_chunks[chunk_idx] = true;
+    pub fn lifetime(&self, x: i32, y: i32) -> u16 {
+        let (pos, index) = chunk_coords(x, y);
+        self.chunks.get(&pos).map_or(0, |chunk| chunk.lifetimes[index])
+    }
+
+    pub fn set_lifetime(&mut self, x: i32, y: i32, lifetime: u16) {
+        let (pos, index) = chunk_coords(x, y);
+        if let Some(chunk) = self.chunks.get_mut(&pos) {
+            chunk.lifetimes[index] = lifetime;
+            // Counting down a lifetime is activity too, even when nothing moves
+            chunk.awake = true;
         }
     }
 
-    pub fn set_rect(&mut self, x: i32, y: i32, w: i32, h: i32, material: Material) {
-        for dy in 0..h {
-            for dx in 0..w {
-                self.set(x + dx, y + dy, material);
+    pub fn shade(&self, x: i32, y: i32) -> u8 {
+        let (pos, index) = chunk_coords(x, y);
+        self.chunks.get(&pos).map_or(0, |chunk| chunk.shades[index])
+    }
+
+    /// Override a pixel's shade, e.g. to keep a grain's colour after it moves
+    pub fn set_shade(&mut self, x: i32, y: i32, shade: u8) {
+        let (pos, index) = chunk_coords(x, y);
+        if let Some(chunk) = self.chunks.get_mut(&pos) {
+            chunk.shades[index] = shade;
+            chunk.modified = true;
+            chunk.dirty = true;
+        }
+    }
+
+    /// Keep a chunk simulating next tick after a change at `index`.
+    /// Changes on the chunk's edge can let pixels in the neighbouring chunks move, so wake them too.
+    fn wake(&mut self, pos: IVec2, index: usize) {
+        let (local_x, local_y) = (index as i32 % CHUNK_SIZE, index as i32 / CHUNK_SIZE);
+        let x_range = if local_x == 0 { -1 } else { 0 }..=if local_x == CHUNK_SIZE - 1 { 1 } else { 0 };
+        let y_range = if local_y == 0 { -1 } else { 0 }..=if local_y == CHUNK_SIZE - 1 { 1 } else { 0 };
+        for dy in y_range {
+            for dx in x_range.clone() {
+                if let Some(chunk) = self.chunks.get_mut(&(pos + IVec2::new(dx, dy))) {
+                    chunk.awake = true;
+                }
             }
         }
     }
 
-    pub fn set_circle(&mut self, cx: i32, cy: i32, radius: i32, material: Material) {
-        let r_sq = radius * radius;
-        for y in (cy - radius)..=(cy + radius) {
-            for x in (cx - radius)..=(cx + radius) {
-                let dx = x - cx;
-                let dy = y - cy;
-                if dx * dx + dy * dy <= r_sq {
-                    self.set(x, y, material);
+    /// Rebuild the ground collider of the chunk with a ground change at `index`. Each
+    /// chunk's outline runs up to its right and lower neighbours' first pixels, so changes
+    /// on the left or top edge also change the outlines of the chunks left of and above it.
+    fn mark_outline_stale(&mut self, pos: IVec2, index: usize) {
+        let (local_x, local_y) = (index as i32 % CHUNK_SIZE, index as i32 / CHUNK_SIZE);
+        let x_range = if local_x == 0 { -1 } else { 0 }..=0;
+        let y_range = if local_y == 0 { -1 } else { 0 }..=0;
+        for dy in y_range {
+            for dx in x_range.clone() {
+                if let Some(chunk) = self.chunks.get_mut(&(pos + IVec2::new(dx, dy))) {
+                    chunk.outline_stale = true;
                 }
             }
         }
     }
+
+    /// Keep the chunk at (x, y) simulating next tick even though nothing changed,
+    /// for pixels that are waiting on a random chance to move
+    pub fn keep_awake(&mut self, x: i32, y: i32) {
+        if let Some(chunk) = self.chunks.get_mut(&chunk_coords(x, y).0) {
+            chunk.awake = true;
+        }
+    }
+
+    /// Chunks that need simulating this tick. Clears their flags, so they go to
+    /// sleep unless something in them changes again while they're simulated.
+    pub fn take_awake_chunks(&mut self) -> Vec<IVec2> {
+        let mut awake = Vec::new();
+        for (pos, chunk) in self.chunks.iter_mut() {
+            if std::mem::take(&mut chunk.awake) {
+                awake.push(*pos);
+            }
+        }
+        awake
+    }
+
+    /// Pixels that carried weight and were removed since this was last called, for
+    /// `detect_floating_chunks` to check around. Clears the list.
+    pub fn take_support_lost(&mut self) -> Vec<(i32, i32)> {
+        std::mem::take(&mut self.support_lost)
+    }
+
+    /// Pixels where a solid was added or removed since this was last called, for
+    /// `break_overloaded_pixels` to work out loads around. Clears the list.
+    pub fn take_load_changed(&mut self) -> Vec<(i32, i32)> {
+        std::mem::take(&mut self.load_changed)
+    }
+
+    /// Chunks whose textures are out of date. Clears their flags.
+    pub fn take_dirty_chunks(&mut self) -> Vec<IVec2> {
+        let mut dirty = Vec::new();
+        for (pos, chunk) in self.chunks.iter_mut() {
+            if std::mem::take(&mut chunk.dirty) {
+                dirty.push(*pos);
+            }
+        }
+        dirty
+    }
+
+    /// Chunks whose pixels changed since their lighting was worked out. Clears their flags.
+    pub fn take_unlit_chunks(&mut self) -> Vec<IVec2> {
+        let mut unlit = Vec::new();
+        for (pos, chunk) in self.chunks.iter_mut() {
+            if std::mem::take(&mut chunk.unlit) {
+                unlit.push(*pos);
+            }
+        }
+        unlit
+    }
+
+    /// Chunks whose ground colliders are out of date. Clears their flags.
+    pub fn take_stale_outlines(&mut self) -> Vec<IVec2> {
+        let mut stale = Vec::new();
+        for (pos, chunk) in self.chunks.iter_mut() {
+            if std::mem::take(&mut chunk.outline_stale) {
+                stale.push(*pos);
+            }
+        }
+        stale
+    }
+
+    /// Whether a rigid body covers (x, y) this frame
+    pub fn is_covered_by_body(&self, x: i32, y: i32) -> bool {
+        let (pos, index) = chunk_coords(x, y);
+        self.body_cover.get(&pos).is_some_and(|cover| cover[index])
+    }
+
+    /// Which pixels of a chunk rigid bodies cover, if any
+    pub fn chunk_body_cover(&self, pos: IVec2) -> Option<&[bool]> {
+        self.body_cover.get(&pos).map(Vec::as_slice)
+    }
+
+    /// Replace the pixels rigid bodies cover. Chunks where that changed, and their
+    /// neighbours, are woken so whatever rested on a body that moved away falls.
+    pub fn set_body_cover(&mut self, pixels: impl IntoIterator<Item = (i32, i32)>) {
+        let mut cover: HashMap<IVec2, Vec<bool>> = HashMap::new();
+        for (x, y) in pixels {
+            let (pos, index) = chunk_coords(x, y);
+            cover.entry(pos).or_insert_with(|| vec![false; (CHUNK_SIZE * CHUNK_SIZE) as usize])[index] = true;
+        }
+
+        let old = std::mem::replace(&mut self.body_cover, cover);
+        let mut changed: Vec<IVec2> = old
+            .iter()
+            .filter(|(pos, pixels)| self.body_cover.get(*pos) != Some(*pixels))
+            .map(|(pos, _)| *pos)
+            .collect();
+        changed.extend(self.body_cover.keys().filter(|pos| !old.contains_key(*pos)));
+        for pos in changed {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    if let Some(chunk) = self.chunks.get_mut(&(pos + IVec2::new(dx, dy))) {
+                        chunk.awake = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Light reaching (x, y); none outside the world or in unloaded chunks
+    pub fn light(&self, x: i32, y: i32) -> Light {
+        let (pos, index) = chunk_coords(x, y);
+        self.chunks.get(&pos).map_or(Light::default(), |chunk| chunk.light[index])
+    }
+
+    /// Replace a chunk's lighting, redrawing it if anything changed
+    pub fn set_chunk_light(&mut self, pos: IVec2, light: Vec<Light>) {
+        if let Some(chunk) = self.chunks.get_mut(&pos) {
+            if chunk.light != light {
+                chunk.light = light;
+                chunk.dirty = true;
+            }
+        }
+    }
+
+    /// Redraw every loaded chunk, e.g. when the sunlight changes
+    pub fn mark_all_dirty(&mut self) {
+        for chunk in self.chunks.values_mut() {
+            chunk.dirty = true;
+        }
+    }
+
+    pub fn awake_chunk_count(&self) -> usize {
+        self.chunks.values().filter(|chunk| chunk.awake).count()
+    }
+
+    /// Set a material along with how many ticks it lives for
+    pub fn set_with_lifetime(&mut self, x: i32, y: i32, material: Material, lifetime: u16) {
+        self.set(x, y, material);
+        self.set_lifetime(x, y, lifetime);
+    }
+
+    pub fn chunk(&self, pos: IVec2) -> Option<&Chunk> {
+        self.chunks.get(&pos)
+    }
+
+    /// Materials in a rectangle (max exclusive) row by row, copied a run at a time
+    /// from each chunk it overlaps. Unloaded parts read as air.
+    pub fn materials_in(&self, rect: IRect) -> Vec<Material> {
+        let mut materials = Vec::with_capacity((rect.width() * rect.height()).max(0) as usize);
+        for y in rect.min.y..rect.max.y {
+            let mut x = rect.min.x;
+            while x < rect.max.x {
+                let (pos, index) = chunk_coords(x, y);
+                let run = (CHUNK_SIZE - x.rem_euclid(CHUNK_SIZE)).min(rect.max.x - x) as usize;
+                match self.chunks.get(&pos) {
+                    Some(chunk) => materials.extend_from_slice(&chunk.pixels[index..index + run]),
+                    None => materials.extend(std::iter::repeat_n(Material::AIR, run)),
+                }
+                x += run as i32;
+            }
+        }
+        materials
+    }
+
+    pub fn is_chunk_loaded(&self, pos: IVec2) -> bool {
+        self.chunks.contains_key(&pos)
+    }
+
+    /// Add a chunk, waking it and its neighbours so anything resting against
+    /// the old edge of the loaded area can settle
+    pub fn insert_chunk(&mut self, pos: IVec2, mut chunk: Chunk) {
+        chunk.awake = true;
+        chunk.dirty = true;
+        chunk.unlit = true;
+        chunk.outline_stale = true;
+        self.chunks.insert(pos, chunk);
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if let Some(neighbour) = self.chunks.get_mut(&(pos + IVec2::new(dx, dy))) {
+                    neighbour.awake = true;
+                }
+            }
+        }
+        self.mark_seams_stale(pos);
+    }
+
+    pub fn remove_chunk(&mut self, pos: IVec2) -> Option<Chunk> {
+        let chunk = self.chunks.remove(&pos);
+        if chunk.is_some() {
+            self.mark_seams_stale(pos);
+        }
+        chunk
+    }
+
+    /// The chunks left of and above `pos` outline up to its first pixels, so their ground
+    /// colliders change when it's loaded or unloaded
+    fn mark_seams_stale(&mut self, pos: IVec2) {
+        for offset in [IVec2::new(-1, 0), IVec2::new(0, -1), IVec2::new(-1, -1)] {
+            if let Some(neighbour) = self.chunks.get_mut(&(pos + offset)) {
+                neighbour.outline_stale = true;
+            }
+        }
+    }
+
+    /// Unload everything, e.g. before loading a saved game
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.support_lost.clear();
+        self.load_changed.clear();
+        self.body_cover.clear();
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = (IVec2, &Chunk)> {
+        self.chunks.iter().map(|(pos, chunk)| (*pos, chunk))
+    }
+}
+
+impl PixelCanvas for PixelWorld {
+    fn set(&mut self, x: i32, y: i32, material: Material) {
+        PixelWorld::set(self, x, y, material)
+    }
 }
 
+/// Sprite showing one loaded chunk
 #[derive(Component)]
-pub struct PixelRenderer {
+pub struct ChunkSprite {
+    pos: IVec2,
     image_handle: Handle<Image>,
 }
 
-pub fn setup_renderer(
+/// Keep one sprite per loaded chunk and copy changed chunks' pixels into their images.
+/// Only dirty chunks are rewritten, so only their textures are uploaded again.
+pub fn render_pixels(
     mut commands: Commands,
+    mut world: ResMut<PixelWorld>,
+    clock: Res<DayNightClock>,
+    sprites: Query<(Entity, &ChunkSprite)>,
     mut images: ResMut<Assets<Image>>,
-    world: Res<PixelWorld>,
 ) {
-    // Create image for rendering pixels
-    let image = Image::new_fill(
-        bevy::render::render_resource::Extent3d {
-            width: world.width as u32,
-            height: world.height as u32,
-            depth_or_array_layers: 1,
-        },
-        bevy::render::render_resource::TextureDimension::D2,
-        &[0, 0, 0, 255],
-        bevy::render::render_resource::TextureFormat::Rgba8UnormSrgb,
-        bevy::render::render_asset::RenderAssetUsages::MAIN_WORLD | bevy::render::render_asset::RenderAssetUsages::RENDER_WORLD,
-    );
-
-    let image_handle = images.add(image);
-
-    commands.spawn((
-        Sprite::from_image(image_handle.clone()),
-        Transform::from_xyz(0.0, 0.0, 0.0),
-        PixelRenderer { image_handle: image_handle.clone() },
-    ));
-}
+    let dirty = world.take_dirty_chunks();
 
-pub fn update_pixels(mut world: ResMut<PixelWorld>, time: Res<Time>) {
-    let mut rng = rand::thread_rng();
-    let width = world.width;
-    let height = world.height;
-
-    // Update at fixed rate (60 times per second) for smooth physics
-    world.update_timer += time.delta_secs();
-    if world.update_timer < 1.0 / 60.0 {
-        return;
-    }
-    world.update_timer = 0.0;
-
-    // Create a copy for reading while we write
-    let old_pixels = world.pixels.clone();
-
-    // Scan from bottom to top for better sand settling performance
-    for y in (0..height - 1).rev() {
-        for x in 0..width {
-            let idx = y * width + x;
-            let material = old_pixels[idx];
-
-            match material {
-                Material::Sand => {
-                    // Sand falls down
-                    let below = world.get(x as i32, y as i32 + 1);
-                    if below == Material::Air {
-                        world.set(x as i32, y as i32, Material::Air);
-                        world.set(x as i32, y as i32 + 1, Material::Sand);
-                    } else if below.density() < material.density() {
-                        // Fall through lighter materials
-                        world.set(x as i32, y as i32, below);
-                        world.set(x as i32, y as i32 + 1, Material::Sand);
-                    } else {
-                        // Try diagonal
-                        let dir = if rng.gen_bool(0.5) { -1 } else { 1 };
-                        let diag = world.get(x as i32 + dir, y as i32 + 1);
-                        if diag == Material::Air {
-                            world.set(x as i32, y as i32, Material::Air);
-                            world.set(x as i32 + dir, y as i32 + 1, Material::Sand);
-                        }
-                    }
-                }
-                Material::Wood | Material::Dirt | Material::Leaf | Material::Fiber => {
-                    // Solid materials don't move
+    // Drop sprites for chunks that were unloaded
+    let mut shown = HashMap::new();
+    for (entity, sprite) in sprites.iter() {
+        if world.is_chunk_loaded(sprite.pos) {
+            shown.insert(sprite.pos, sprite.image_handle.clone());
+        } else {
+            images.remove(&sprite.image_handle);
+            commands.entity(entity).despawn();
+        }
+    }
+
+    // Convert each material's palette to bytes once instead of per pixel
+    let registry = MaterialRegistry::global();
+    let palettes: Vec<Vec<[u8; 4]>> = registry
+        .iter()
+        .map(|(_, props)| {
+            (0..props.palette.len().max(1))
+                .map(|shade| props.shade_color(shade as u8).to_srgba().to_u8_array())
+                .collect()
+        })
+        .collect();
+    let empty: Vec<bool> = registry.iter().map(|(_, props)| props.state == MaterialState::Empty).collect();
+    let sunlight = clock.sunlight();
+
+    let mut new_sprites = Vec::new();
+    for pos in dirty {
+        let Some(chunk) = world.chunks.get(&pos) else {
+            continue;
+        };
+        let handle = match shown.get(&pos) {
+            Some(handle) => handle.clone(),
+            None => {
+                let handle = images.add(Image::new_fill(
+                    bevy::render::render_resource::Extent3d {
+                        width: CHUNK_SIZE as u32,
+                        height: CHUNK_SIZE as u32,
+                        depth_or_array_layers: 1,
+                    },
+                    bevy::render::render_resource::TextureDimension::D2,
+                    &[0, 0, 0, 0],
+                    bevy::render::render_resource::TextureFormat::Rgba8UnormSrgb,
+                    bevy::render::render_asset::RenderAssetUsages::MAIN_WORLD | bevy::render::render_asset::RenderAssetUsages::RENDER_WORLD,
+                ));
+                new_sprites.push((pos, handle.clone()));
+                handle
+            }
+        };
+
+        if let Some(image) = images.get_mut(&handle) {
+            if let Some(data) = &mut image.data {
+                // Image rows run top to bottom, the same as pixel y
+                for (i, (material, shade)) in chunk.pixels.iter().zip(&chunk.shades).enumerate() {
+                    let id = material.0 as usize;
+                    let colors = palettes.get(id).unwrap_or(&palettes[0]);
+                    let color = colors[*shade as usize % colors.len()];
+                    let lit = lit_color(color, chunk.light[i], sunlight, empty.get(id).copied().unwrap_or(true));
+                    data[i * 4..i * 4 + 4].copy_from_slice(&lit);
                 }
-                Material::Air => {}
             }
         }
     }
+
+    for (pos, image_handle) in new_sprites {
+        // Chunk sprites are centred on the middle of the chunk
+        let center = WorldService::pixel_to_world(
+            pos.x * CHUNK_SIZE + CHUNK_SIZE / 2,
+            pos.y * CHUNK_SIZE + CHUNK_SIZE / 2,
+        );
+        commands.spawn((
+            Sprite::from_image(image_handle.clone()),
+            Transform::from_xyz(center.x, center.y, 0.0),
+            ChunkSprite { pos, image_handle },
+        ));
+    }
 }
 
-pub fn render_pixels(
-    world: Res<PixelWorld>,
-    query: Query<&PixelRenderer>,
-    mut images: ResMut<Assets<Image>>,
-) {
-    let Ok(renderer) = query.single() else {
-        return;
-    };
-
-    // Only re-render if the world has changed
-    if !world.is_changed() {
-        return;
-    }
-
-    if let Some(image) = images.get_mut(&renderer.image_handle) {
-        if let Some(data) = &mut image.data {
-            // Update image pixels (y=0 at top, increases downward)
-            for y in 0..world.height {
-                for x in 0..world.width {
-                    let material = world.get(x as i32, y as i32);
-                    let color = material.color();
-                    let idx = (y * world.width + x) * 4;
-
-                    data[idx] = (color.to_srgba().red * 255.0) as u8;
-                    data[idx + 1] = (color.to_srgba().green * 255.0) as u8;
-                    data[idx + 2] = (color.to_srgba().blue * 255.0) as u8;
-                    data[idx + 3] = (color.to_srgba().alpha * 255.0) as u8;
-                }
+/// Worlds for tests to build on
+#[cfg(test)]
+pub mod test_support {
+    use bevy::prelude::*;
+    use super::PixelWorld;
+    use crate::world::chunk::{Chunk, CHUNK_SIZE};
+    use crate::world::Material;
+
+    /// Height of the worlds tests build, the same as the game's
+    pub const WORLD_HEIGHT: i32 = 640;
+
+    /// `chunks_wide` empty chunks across, the full height of the world
+    pub fn empty_world(chunks_wide: i32) -> PixelWorld {
+        let mut world = PixelWorld::new(WORLD_HEIGHT);
+        for cy in 0..WORLD_HEIGHT / CHUNK_SIZE {
+            for cx in 0..chunks_wide {
+                world.insert_chunk(IVec2::new(cx, cy), Chunk::default());
+            }
+        }
+        world
+    }
+
+    /// `chunks_wide` chunks across, the full height of the world, with `ground` from row 600 down
+    pub fn world_with_ground(chunks_wide: i32, ground: Material) -> PixelWorld {
+        let mut world = empty_world(chunks_wide);
+        fill(&mut world, IRect::new(0, 600, chunks_wide * CHUNK_SIZE, WORLD_HEIGHT), ground);
+        world
+    }
+
+    pub fn fill(world: &mut PixelWorld, rect: IRect, material: Material) {
+        for y in rect.min.y..rect.max.y {
+            for x in rect.min.x..rect.max.x {
+                world.set(x, y, material);
             }
         }
     }
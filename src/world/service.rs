@@ -1,13 +1,15 @@
 use bevy::prelude::*;
 use super::materials::Material;
 use super::pixel_world::PixelWorld;
-use crate::constants::{PIXEL_TO_WORLD_OFFSET_X, PIXEL_TO_WORLD_OFFSET_Y, WORLD_PIXEL_WIDTH, WORLD_PIXEL_HEIGHT};
+use super::chunk::PixelCanvas;
+use crate::constants::{PIXEL_TO_WORLD_OFFSET_X, PIXEL_TO_WORLD_OFFSET_Y, WORLD_PIXEL_HEIGHT};
 
 /// Service layer for world operations that provides a facade over PixelWorld.
 /// This centralizes coordinate conversions and common world manipulation patterns.
 #[derive(Resource)]
 pub struct WorldService;
 
+#[allow(dead_code)] // Helpers for tools to build on; not every one has a caller yet
 impl WorldService {
     /// Convert pixel coordinates to world coordinates
     /// World coordinates have (0, 0) at the center of the screen
@@ -18,18 +20,51 @@ impl WorldService {
         )
     }
 
+    /// World coordinates of a pixel's centre; `pixel_to_world` gives its top-left corner
+    pub fn pixel_center_to_world(pixel_x: i32, pixel_y: i32) -> Vec2 {
+        Self::pixel_to_world(pixel_x, pixel_y) + Vec2::new(0.5, -0.5)
+    }
+
+    /// Convert a sub-pixel position (like the player's) to world coordinates
+    pub fn pixel_pos_to_world(pixel_pos: Vec2) -> Vec2 {
+        Vec2::new(
+            pixel_pos.x - PIXEL_TO_WORLD_OFFSET_X,
+            PIXEL_TO_WORLD_OFFSET_Y - pixel_pos.y,
+        )
+    }
+
+    /// World position under the mouse cursor, following the camera's position and zoom.
+    /// Returns None if the cursor is outside the window.
+    pub fn cursor_to_world(
+        windows: &Query<&Window>,
+        cameras: &Query<(&Camera, &GlobalTransform)>,
+    ) -> Option<Vec2> {
+        let cursor = windows.single().ok()?.cursor_position()?;
+        let (camera, camera_transform) = cameras.single().ok()?;
+        camera.viewport_to_world_2d(camera_transform, cursor).ok()
+    }
+
+    /// Pixel under the mouse cursor
+    pub fn cursor_to_pixel(
+        windows: &Query<&Window>,
+        cameras: &Query<(&Camera, &GlobalTransform)>,
+    ) -> Option<(i32, i32)> {
+        Self::cursor_to_world(windows, cameras).map(Self::world_to_pixel)
+    }
+
     /// Convert world coordinates to pixel coordinates
     /// Returns None if the position is outside the world bounds
     pub fn world_to_pixel(world_pos: Vec2) -> (i32, i32) {
-        let pixel_x = (world_pos.x + PIXEL_TO_WORLD_OFFSET_X) as i32;
-        let pixel_y = (PIXEL_TO_WORLD_OFFSET_Y - world_pos.y) as i32;
+        // Floor rather than truncate so positions left of pixel 0 map to negative pixels
+        let pixel_x = (world_pos.x + PIXEL_TO_WORLD_OFFSET_X).floor() as i32;
+        let pixel_y = (PIXEL_TO_WORLD_OFFSET_Y - world_pos.y).floor() as i32;
         (pixel_x, pixel_y)
     }
 
-    /// Check if world coordinates are within bounds
+    /// Check if world coordinates are within the world's rows (it is unbounded horizontally)
     pub fn is_in_bounds(world_pos: Vec2) -> bool {
-        let (px, py) = Self::world_to_pixel(world_pos);
-        px >= 0 && px < WORLD_PIXEL_WIDTH as i32 && py >= 0 && py < WORLD_PIXEL_HEIGHT as i32
+        let (_, py) = Self::world_to_pixel(world_pos);
+        (0..WORLD_PIXEL_HEIGHT).contains(&py)
     }
 
     /// Get material at world position
@@ -64,10 +99,10 @@ impl WorldService {
 
                 if dx * dx + dy * dy <= r_sq {
                     let material = world.get(px, py);
-                    if material != Material::Air {
+                    if material != Material::AIR {
                         let material_world_pos = Self::pixel_to_world(px, py);
                         broken_materials.push((material, material_world_pos));
-                        world.set(px, py, Material::Air);
+                        world.set(px, py, Material::AIR);
                     }
                 }
             }
@@ -79,7 +114,7 @@ impl WorldService {
     /// Check if there's a solid collision at world position (for player physics)
     pub fn has_collision_at(world: &PixelWorld, world_pos: Vec2) -> bool {
         let material = Self::get_material_at_world(world, world_pos);
-        material != Material::Air
+        material.is_solid()
     }
 
     /// Get all pixels in a rectangular region (in pixel coordinates)
@@ -96,7 +131,7 @@ impl WorldService {
         for y in min_y..=max_y {
             for x in min_x..=max_x {
                 let material = world.get(x, y);
-                if material != Material::Air {
+                if material != Material::AIR {
                     pixels.push((x, y, material));
                 }
             }
@@ -115,9 +150,9 @@ impl WorldService {
 
         for (x, y) in pixels {
             let material = world.get(*x, *y);
-            if material != Material::Air {
+            if material != Material::AIR {
                 materials.push(material);
-                world.set(*x, *y, Material::Air);
+                world.set(*x, *y, Material::AIR);
             }
         }
 
@@ -0,0 +1,598 @@
+use bevy::prelude::*;
+use bevy::tasks::{ComputeTaskPool, TaskPool};
+use bevy::platform::collections::HashMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+use super::chunk::{pixel_shade, CHUNK_SIZE};
+use super::fire;
+use super::materials::{Material, MaterialRegistry, MaterialState};
+use super::particles::ParticleSpawnEvent;
+use super::pixel_world::PixelWorld;
+use super::service::WorldService;
+use super::terrain::{feature_hash, WorldSeed};
+
+/// How far past its own chunk a chunk's update may reach.
+/// Chunks updated in the same phase are two chunks apart, so their windows never overlap
+/// as long as this is at most half a chunk.
+const WINDOW_MARGIN: i32 = CHUNK_SIZE / 2;
+const WINDOW_SIZE: i32 = CHUNK_SIZE + 2 * WINDOW_MARGIN;
+
+/// Checkerboard phases by chunk (x, y) parity. Every chunk in a phase can be
+/// updated at the same time without touching the same pixels as another.
+const PHASES: [IVec2; 4] = [IVec2::new(0, 0), IVec2::new(1, 0), IVec2::new(0, 1), IVec2::new(1, 1)];
+
+/// Salt for the per-chunk random streams
+const SIMULATION_SALT: u64 = 0x5117;
+
+/// Pixel access the simulation rules need, so they run the same on the live
+/// world or on a chunk's window during a parallel update
+pub trait PixelGrid {
+    fn in_bounds(&self, x: i32, y: i32) -> bool;
+    fn get(&self, x: i32, y: i32) -> Material;
+    fn set(&mut self, x: i32, y: i32, material: Material);
+    fn lifetime(&self, x: i32, y: i32) -> u16;
+    fn set_lifetime(&mut self, x: i32, y: i32, lifetime: u16);
+    fn shade(&self, x: i32, y: i32) -> u8;
+    fn set_shade(&mut self, x: i32, y: i32, shade: u8);
+    /// Whether a rigid body covers (x, y), so loose pixels can't move there
+    fn covered_by_body(&self, x: i32, y: i32) -> bool;
+    /// Keep simulating around (x, y) next tick, for pixels waiting on a random chance to move
+    fn keep_awake(&mut self, x: i32, y: i32);
+
+    /// Set a material along with how many ticks it lives for
+    fn set_with_lifetime(&mut self, x: i32, y: i32, material: Material, lifetime: u16) {
+        self.set(x, y, material);
+        self.set_lifetime(x, y, lifetime);
+    }
+
+    /// Swap two pixels, carrying their lifetimes and shades with them
+    fn swap(&mut self, x: i32, y: i32, nx: i32, ny: i32) {
+        if !self.in_bounds(x, y) || !self.in_bounds(nx, ny) {
+            return;
+        }
+        let a = (self.get(x, y), self.lifetime(x, y), self.shade(x, y));
+        let b = (self.get(nx, ny), self.lifetime(nx, ny), self.shade(nx, ny));
+        self.set_with_lifetime(x, y, b.0, b.1);
+        self.set_shade(x, y, b.2);
+        self.set_with_lifetime(nx, ny, a.0, a.1);
+        self.set_shade(nx, ny, a.2);
+    }
+}
+
+impl PixelGrid for PixelWorld {
+    fn in_bounds(&self, x: i32, y: i32) -> bool {
+        PixelWorld::in_bounds(self, x, y)
+    }
+
+    fn get(&self, x: i32, y: i32) -> Material {
+        PixelWorld::get(self, x, y)
+    }
+
+    fn set(&mut self, x: i32, y: i32, material: Material) {
+        PixelWorld::set(self, x, y, material)
+    }
+
+    fn lifetime(&self, x: i32, y: i32) -> u16 {
+        PixelWorld::lifetime(self, x, y)
+    }
+
+    fn set_lifetime(&mut self, x: i32, y: i32, lifetime: u16) {
+        PixelWorld::set_lifetime(self, x, y, lifetime)
+    }
+
+    fn shade(&self, x: i32, y: i32) -> u8 {
+        PixelWorld::shade(self, x, y)
+    }
+
+    fn set_shade(&mut self, x: i32, y: i32, shade: u8) {
+        PixelWorld::set_shade(self, x, y, shade)
+    }
+
+    fn covered_by_body(&self, x: i32, y: i32) -> bool {
+        PixelWorld::is_covered_by_body(self, x, y)
+    }
+
+    fn keep_awake(&mut self, x: i32, y: i32) {
+        PixelWorld::keep_awake(self, x, y)
+    }
+}
+
+/// Simulation options and timing, shown in the debug menu
+#[derive(Resource)]
+pub struct PixelSimulation {
+    /// Update chunks on the compute task pool instead of one after another.
+    /// Both give identical results.
+    pub multithreaded: bool,
+    /// How long the last tick took
+    pub last_tick: Duration,
+}
+
+impl Default for PixelSimulation {
+    fn default() -> Self {
+        Self {
+            multithreaded: true,
+            last_tick: Duration::ZERO,
+        }
+    }
+}
+
+/// A copy of one chunk plus a margin around it, updated on its own and written back afterwards
+struct ChunkWindow {
+    /// Top-left pixel of the window
+    origin: IVec2,
+    pixels: Vec<Material>,
+    lifetimes: Vec<u16>,
+    shades: Vec<u8>,
+    /// Whether each pixel is in a loaded chunk; the rest reads as air and can't be entered
+    loaded: Vec<bool>,
+    /// Whether a rigid body covers each pixel
+    body_cover: Vec<bool>,
+    changed: Vec<bool>,
+    wake: Vec<(i32, i32)>,
+}
+
+impl ChunkWindow {
+    fn copy_from(world: &PixelWorld, chunk: IVec2) -> Self {
+        let len = (WINDOW_SIZE * WINDOW_SIZE) as usize;
+        let mut window = Self {
+            origin: chunk * CHUNK_SIZE - IVec2::splat(WINDOW_MARGIN),
+            pixels: vec![Material::AIR; len],
+            lifetimes: vec![0; len],
+            shades: vec![0; len],
+            loaded: vec![false; len],
+            body_cover: vec![false; len],
+            changed: vec![false; len],
+            wake: Vec::new(),
+        };
+
+        // Copy the overlapping part of the chunk and each of its neighbours a row at a time
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let pos = chunk + IVec2::new(dx, dy);
+                let Some(source) = world.chunk(pos) else {
+                    continue;
+                };
+                let min = (pos * CHUNK_SIZE).max(window.origin);
+                let max = ((pos + IVec2::ONE) * CHUNK_SIZE).min(window.origin + IVec2::splat(WINDOW_SIZE));
+                for y in min.y..max.y {
+                    let from = ((y - pos.y * CHUNK_SIZE) * CHUNK_SIZE + min.x - pos.x * CHUNK_SIZE) as usize;
+                    let to = ((y - window.origin.y) * WINDOW_SIZE + min.x - window.origin.x) as usize;
+                    let width = (max.x - min.x) as usize;
+                    window.pixels[to..to + width].copy_from_slice(&source.pixels[from..from + width]);
+                    window.lifetimes[to..to + width].copy_from_slice(&source.lifetimes[from..from + width]);
+                    window.shades[to..to + width].copy_from_slice(&source.shades[from..from + width]);
+                    window.loaded[to..to + width].fill(true);
+                    if let Some(cover) = world.chunk_body_cover(pos) {
+                        window.body_cover[to..to + width].copy_from_slice(&cover[from..from + width]);
+                    }
+                }
+            }
+        }
+        window
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        let (local_x, local_y) = (x - self.origin.x, y - self.origin.y);
+        let inside = (0..WINDOW_SIZE).contains(&local_x) && (0..WINDOW_SIZE).contains(&local_y);
+        inside
+            .then_some((local_y * WINDOW_SIZE + local_x) as usize)
+            .filter(|&index| self.loaded[index])
+    }
+
+    /// Copy changed pixels back into the world, which wakes and dirties their chunks
+    fn write_back(self, world: &mut PixelWorld) {
+        for (index, _) in self.changed.iter().enumerate().filter(|(_, changed)| **changed) {
+            let x = self.origin.x + index as i32 % WINDOW_SIZE;
+            let y = self.origin.y + index as i32 / WINDOW_SIZE;
+            let (material, lifetime) = (self.pixels[index], self.lifetimes[index]);
+            if world.get(x, y) != material {
+                world.set_with_lifetime(x, y, material, lifetime);
+            } else if world.lifetime(x, y) != lifetime {
+                world.set_lifetime(x, y, lifetime);
+            }
+            if world.shade(x, y) != self.shades[index] {
+                world.set_shade(x, y, self.shades[index]);
+            }
+        }
+        for (x, y) in self.wake {
+            world.keep_awake(x, y);
+        }
+    }
+}
+
+impl PixelGrid for ChunkWindow {
+    fn in_bounds(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some()
+    }
+
+    fn get(&self, x: i32, y: i32) -> Material {
+        self.index(x, y).map_or(Material::AIR, |index| self.pixels[index])
+    }
+
+    fn set(&mut self, x: i32, y: i32, material: Material) {
+        if let Some(index) = self.index(x, y) {
+            self.pixels[index] = material;
+            self.lifetimes[index] = 0;
+            self.shades[index] = pixel_shade(material, x, y);
+            self.changed[index] = true;
+        }
+    }
+
+    fn lifetime(&self, x: i32, y: i32) -> u16 {
+        self.index(x, y).map_or(0, |index| self.lifetimes[index])
+    }
+
+    fn set_lifetime(&mut self, x: i32, y: i32, lifetime: u16) {
+        if let Some(index) = self.index(x, y) {
+            self.lifetimes[index] = lifetime;
+            self.changed[index] = true;
+        }
+    }
+
+    fn shade(&self, x: i32, y: i32) -> u8 {
+        self.index(x, y).map_or(0, |index| self.shades[index])
+    }
+
+    fn set_shade(&mut self, x: i32, y: i32, shade: u8) {
+        if let Some(index) = self.index(x, y) {
+            self.shades[index] = shade;
+            self.changed[index] = true;
+        }
+    }
+
+    fn covered_by_body(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some_and(|index| self.body_cover[index])
+    }
+
+    fn keep_awake(&mut self, x: i32, y: i32) {
+        self.wake.push((x, y));
+    }
+}
+
+/// Everything one chunk's update needs, so it can be moved onto another thread
+struct ChunkUpdate {
+    chunk: IVec2,
+    window: ChunkWindow,
+    /// The chunk's pixels at the start of the tick
+    start: Vec<Material>,
+    rng: StdRng,
+    sparks: Vec<(i32, i32)>,
+}
+
+impl ChunkUpdate {
+    fn run(&mut self, active: &[bool], height: i32) {
+        // Scan from bottom to top within the chunk
+        for local_y in (0..CHUNK_SIZE).rev() {
+            let y = self.chunk.y * CHUNK_SIZE + local_y;
+            if y >= height - 1 {
+                continue;
+            }
+            for local_x in 0..CHUNK_SIZE {
+                let material = self.start[(local_y * CHUNK_SIZE + local_x) as usize];
+                if !active.get(material.0 as usize).copied().unwrap_or(false) {
+                    continue;
+                }
+
+                // Skip pixels that were already displaced this tick
+                let x = self.chunk.x * CHUNK_SIZE + local_x;
+                let window = &mut self.window;
+                if window.get(x, y) != material {
+                    continue;
+                }
+
+                let rng = &mut self.rng;
+                match material {
+                    Material::EMBER => fire::update_ember(window, rng, x, y, &mut self.sparks),
+                    Material::FIRE => fire::update_flame(window, rng, x, y),
+                    Material::SMOKE => fire::update_smoke(window, rng, x, y),
+                    _ if material.is_powder() => update_powder(window, rng, x, y, material),
+                    _ if material.is_liquid() => update_liquid(window, rng, x, y, material),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+pub fn update_pixels(
+    mut world: ResMut<PixelWorld>,
+    mut simulation: ResMut<PixelSimulation>,
+    seed: Res<WorldSeed>,
+    mut particle_events: EventWriter<ParticleSpawnEvent>,
+    time: Res<Time>,
+) {
+    // Update at fixed rate (60 times per second) for smooth physics
+    world.update_timer += time.delta_secs();
+    if world.update_timer < 1.0 / 60.0 {
+        return;
+    }
+    world.update_timer = 0.0;
+    world.tick += 1;
+
+    // Sleeping chunks are skipped entirely; only chunks where something changed last tick run
+    let awake = world.take_awake_chunks();
+    if awake.is_empty() {
+        return;
+    }
+    let started = Instant::now();
+
+    // Only these materials ever move or change on their own
+    let active: Vec<bool> = MaterialRegistry::global()
+        .iter()
+        .map(|(material, props)| {
+            matches!(material, Material::EMBER | Material::FIRE | Material::SMOKE)
+                || matches!(props.state, MaterialState::Powder | MaterialState::Liquid)
+        })
+        .collect();
+
+    // Snapshot every awake chunk before anything moves, so pixels that
+    // crossed into a chunk updated in a later phase aren't moved twice
+    let mut start: HashMap<IVec2, Vec<Material>> = awake
+        .iter()
+        .filter_map(|pos| world.chunk(*pos).map(|chunk| (*pos, chunk.pixels.clone())))
+        .collect();
+
+    // Each chunk gets its own random stream from the seed and tick, so results
+    // don't depend on which thread ran it or in what order
+    let tick_seed = feature_hash(seed.0, SIMULATION_SALT, world.tick as i64);
+    let mut sparks = Vec::new();
+
+    for phase in PHASES {
+        let mut chunks: Vec<IVec2> = awake
+            .iter()
+            .copied()
+            .filter(|pos| pos.rem_euclid(IVec2::splat(2)) == phase)
+            .collect();
+        chunks.sort_by_key(|pos| (pos.y, pos.x));
+
+        let updates: Vec<ChunkUpdate> = chunks
+            .into_iter()
+            .filter_map(|chunk| {
+                let start = start.remove(&chunk)?;
+                let index = ((chunk.x as i64) << 32) | (chunk.y as u32 as i64);
+                Some(ChunkUpdate {
+                    chunk,
+                    window: ChunkWindow::copy_from(&world, chunk),
+                    start,
+                    rng: StdRng::seed_from_u64(feature_hash(tick_seed, SIMULATION_SALT, index)),
+                    sparks: Vec::new(),
+                })
+            })
+            .collect();
+
+        let (active, height) = (&active, world.height);
+        let updates = if simulation.multithreaded {
+            ComputeTaskPool::get_or_init(TaskPool::default).scope(|scope| {
+                for mut update in updates {
+                    scope.spawn(async move {
+                        update.run(active, height);
+                        update
+                    });
+                }
+            })
+        } else {
+            updates
+                .into_iter()
+                .map(|mut update| {
+                    update.run(active, height);
+                    update
+                })
+                .collect()
+        };
+
+        // Results come back in the order the updates were created, whichever thread finished first
+        for update in updates {
+            sparks.extend(update.sparks);
+            update.window.write_back(&mut world);
+        }
+    }
+
+    simulation.last_tick = started.elapsed();
+
+    for (x, y) in sparks {
+        particle_events.write(ParticleSpawnEvent {
+            position: WorldService::pixel_to_world(x, y),
+            material: Material::EMBER,
+        });
+    }
+}
+
+/// Maximum number of cells a liquid pixel can flow sideways in one tick
+const LIQUID_DISPERSION: i32 = 4;
+
+/// Chance per tick that a liquid touching something absorbent (like dry sand) soaks into it
+const SOAK_CHANCE: f64 = 0.05;
+
+/// Whether `mover` can swap into the cell at (x, y)
+/// Cells outside the world are never free, so liquids don't drain off the edges,
+/// and neither are cells under rigid bodies, so loose pixels rest on them
+fn can_displace(world: &impl PixelGrid, mover: Material, x: i32, y: i32) -> bool {
+    if !world.in_bounds(x, y) || world.covered_by_body(x, y) {
+        return false;
+    }
+    let target = world.get(x, y);
+    target == Material::AIR
+        || target.is_gas()
+        || (target.is_liquid() && target.density() < mover.density())
+}
+
+fn update_powder(world: &mut impl PixelGrid, rng: &mut impl Rng, x: i32, y: i32, material: Material) {
+    // Powder falls down
+    let below = world.get(x, y + 1);
+    if (below == Material::AIR || below.density() < material.density()) && !world.covered_by_body(x, y + 1) {
+        // Fall through air and lighter materials
+        world.swap(x, y, x, y + 1);
+        return;
+    }
+
+    // Try diagonal, picking a random side first
+    let dir = if rng.gen_bool(0.5) { -1 } else { 1 };
+    let Some(side) = [dir, -dir].into_iter().find(|&side| can_displace(world, material, x + side, y + 1)) else {
+        return;
+    };
+
+    // Clumpy powders like wet sand rarely slide, but stay awake until they do
+    let slide_chance = material.props().slide_chance;
+    if slide_chance < 1.0 && !rng.gen_bool(slide_chance) {
+        world.keep_awake(x, y);
+        return;
+    }
+
+    world.swap(x, y, x + side, y + 1);
+}
+
+pub(super) fn update_gas(world: &mut impl PixelGrid, rng: &mut impl Rng, x: i32, y: i32) {
+    // Rise straight up, then diagonally, then drift sideways
+    let dir = if rng.gen_bool(0.5) { -1 } else { 1 };
+    for (dx, dy) in [(0, -1), (dir, -1), (-dir, -1), (dir, 0)] {
+        if world.in_bounds(x + dx, y + dy)
+            && world.get(x + dx, y + dy) == Material::AIR
+            && !world.covered_by_body(x + dx, y + dy)
+        {
+            world.swap(x, y, x + dx, y + dy);
+            return;
+        }
+    }
+}
+
+fn update_liquid(world: &mut impl PixelGrid, rng: &mut impl Rng, x: i32, y: i32, material: Material) {
+    // Soak into neighbouring materials that have a wet variant (e.g. dry sand)
+    let registry = MaterialRegistry::global();
+    for (dx, dy) in [(0, 1), (-1, 0), (1, 0)] {
+        if let Some(wet) = registry.wet_variant(world.get(x + dx, y + dy)) {
+            if rng.gen_bool(SOAK_CHANCE) {
+                world.set(x + dx, y + dy, wet);
+                world.set(x, y, Material::AIR);
+                return;
+            }
+            // Still something left to soak into
+            world.keep_awake(x, y);
+        }
+    }
+
+    // Fall straight down
+    if can_displace(world, material, x, y + 1) {
+        world.swap(x, y, x, y + 1);
+        return;
+    }
+
+    // Then diagonally down
+    let dir = if rng.gen_bool(0.5) { -1 } else { 1 };
+    for side in [dir, -dir] {
+        if can_displace(world, material, x + side, y + 1) {
+            world.swap(x, y, x + side, y + 1);
+            return;
+        }
+    }
+
+    // Otherwise flow sideways to level out, as far as the path is clear
+    for side in [dir, -dir] {
+        let mut target_x = x;
+        for step in 1..=LIQUID_DISPERSION {
+            if !can_displace(world, material, x + side * step, y) {
+                break;
+            }
+            target_x = x + side * step;
+        }
+
+        if target_x != x {
+            world.swap(x, y, target_x, y);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::pixel_world::test_support::{empty_world, fill, WORLD_HEIGHT};
+
+    /// Every loaded chunk's position, pixels, lifetimes and shades
+    type Snapshot = Vec<(IVec2, Vec<Material>, Vec<u16>, Vec<u8>)>;
+
+    /// A world `chunks_wide` chunks across, with stone along the bottom and a mix of sand
+    /// and water filling `falling` (in chunks) to fall onto it
+    fn falling_sand_world(chunks_wide: i32, falling: IRect) -> PixelWorld {
+        let mut world = empty_world(chunks_wide);
+        fill(&mut world, IRect::new(0, WORLD_HEIGHT - CHUNK_SIZE, chunks_wide * CHUNK_SIZE, WORLD_HEIGHT), Material::STONE);
+        for y in falling.min.y * CHUNK_SIZE..falling.max.y * CHUNK_SIZE {
+            for x in falling.min.x * CHUNK_SIZE..falling.max.x * CHUNK_SIZE {
+                let material = if x % 7 == 0 { Material::WATER } else { Material::SAND };
+                world.set(x, y, material);
+            }
+        }
+        world
+    }
+
+    fn simulation_app(world: PixelWorld, multithreaded: bool) -> App {
+        let mut app = App::new();
+        app.insert_resource(world)
+            .insert_resource(PixelSimulation { multithreaded, ..default() })
+            .insert_resource(WorldSeed(7))
+            .init_resource::<Time>()
+            .add_event::<ParticleSpawnEvent>()
+            .add_systems(Update, update_pixels);
+        app
+    }
+
+    /// Run one simulation tick
+    fn tick(app: &mut App) {
+        app.world_mut().resource_mut::<Time>().advance_by(Duration::from_millis(17));
+        app.update();
+    }
+
+    /// Every loaded chunk in a fixed order
+    fn snapshot(app: &App) -> Snapshot {
+        let mut chunks: Vec<_> = app
+            .world()
+            .resource::<PixelWorld>()
+            .chunks()
+            .map(|(pos, chunk)| (pos, chunk.pixels.clone(), chunk.lifetimes.clone(), chunk.shades.clone()))
+            .collect();
+        chunks.sort_by_key(|(pos, ..)| (pos.y, pos.x));
+        chunks
+    }
+
+    /// Simulate falling sand with or without threads and return the final grid
+    fn run_falling_sand(chunks_wide: i32, ticks: usize, multithreaded: bool) -> Snapshot {
+        let falling = IRect::new(0, 0, chunks_wide, 12);
+        let mut app = simulation_app(falling_sand_world(chunks_wide, falling), multithreaded);
+        for _ in 0..ticks {
+            tick(&mut app);
+        }
+        snapshot(&app)
+    }
+
+    #[test]
+    fn threads_give_the_same_result_as_one_after_another() {
+        let single = run_falling_sand(4, 20, false);
+        let threaded = run_falling_sand(4, 20, true);
+        assert!(single == threaded);
+    }
+
+    /// Ticks only cost as much as the chunks they simulate, so the same patch of falling
+    /// sand keeps as many chunks awake in a small world as in one eight times as wide
+    #[test]
+    fn only_chunks_near_falling_sand_stay_awake() {
+        let falling = IRect::new(0, 0, 2, 12);
+        let awake_per_tick = |chunks_wide: i32| {
+            let mut app = simulation_app(falling_sand_world(chunks_wide, falling), true);
+            // Every chunk starts awake; the still ones fall asleep after a tick or two
+            for _ in 0..3 {
+                tick(&mut app);
+            }
+            (0..10)
+                .map(|_| {
+                    tick(&mut app);
+                    app.world().resource::<PixelWorld>().awake_chunk_count()
+                })
+                .collect::<Vec<_>>()
+        };
+        let small = awake_per_tick(4);
+        // The two columns of chunks with sand in them and the one beside them, at most
+        let per_column = WORLD_HEIGHT / CHUNK_SIZE;
+        assert!(small.iter().all(|awake| *awake > 0 && *awake <= 3 * per_column as usize));
+        assert_eq!(awake_per_tick(32), small);
+    }
+}
@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use super::chunk::{Chunk, EncodedChunk, CHUNK_SIZE};
+use super::materials::{Material, MaterialRegistry};
+use super::pixel_world::PixelWorld;
+use super::service::WorldService;
+use super::terrain::{TerrainGenerator, WorldSeed};
+
+/// Chunk columns kept loaded either side of the camera - enough to fill the view at full zoom out
+pub const LOAD_RADIUS: i32 = 20;
+
+/// Chunk columns further than this from the camera are unloaded.
+/// Larger than `LOAD_RADIUS` so walking back and forth over a border doesn't thrash the disk.
+pub const UNLOAD_RADIUS: i32 = 24;
+
+/// Where modified chunks go while they're unloaded, in a directory per seed
+const CACHE_DIR: &str = "world_cache";
+
+/// Disk cache for chunks that were changed and then streamed out.
+/// Unmodified chunks are never written - they're regenerated from the seed instead.
+/// The cache only lives for one session; saves copy whatever is in it.
+#[derive(Resource)]
+pub struct ChunkStore {
+    dir: PathBuf,
+}
+
+/// On-disk form of a cached chunk, carrying its own material names like a save does
+#[derive(Serialize, Deserialize)]
+struct StoredChunk {
+    materials: Vec<String>,
+    chunk: EncodedChunk,
+}
+
+impl ChunkStore {
+    /// Store for the world generated from `seed`, leaving other seeds' caches alone
+    pub fn for_seed(seed: u64) -> Self {
+        Self { dir: PathBuf::from(CACHE_DIR).join(seed.to_string()) }
+    }
+
+    fn path(&self, pos: IVec2) -> PathBuf {
+        self.dir.join(format!("{}_{}.json", pos.x, pos.y))
+    }
+
+    /// Forget every chunk stored for this seed, e.g. when starting a new world
+    pub fn clear(&self) {
+        if self.dir.exists() {
+            info!("Clearing chunk cache {}", self.dir.display());
+            if let Err(e) = fs::remove_dir_all(&self.dir) {
+                warn!("Failed to clear chunk cache {}: {}", self.dir.display(), e);
+            }
+        }
+    }
+
+    pub fn save(&self, pos: IVec2, chunk: &Chunk) -> Result<(), String> {
+        fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        let stored = StoredChunk {
+            materials: material_names(MaterialRegistry::global()),
+            chunk: EncodedChunk::encode(pos, chunk),
+        };
+        let json = serde_json::to_string(&stored).map_err(|e| e.to_string())?;
+        fs::write(self.path(pos), json).map_err(|e| e.to_string())
+    }
+
+    /// A previously stored chunk, or None if it was never modified
+    pub fn load(&self, pos: IVec2) -> Option<Result<Chunk, String>> {
+        let json = fs::read_to_string(self.path(pos)).ok()?;
+        Some(self.decode(&json).map(|(_, chunk)| chunk))
+    }
+
+    fn decode(&self, json: &str) -> Result<(IVec2, Chunk), String> {
+        let stored: StoredChunk = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let remap = material_remap(MaterialRegistry::global(), &stored.materials);
+        stored.chunk.decode(remap)
+    }
+
+    /// Every chunk in the store, still encoded, for writing into a save
+    pub fn all_encoded(&self) -> Vec<EncodedChunk> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|json| match self.decode(&json) {
+                Ok((pos, chunk)) => Some(EncodedChunk::encode(pos, &chunk)),
+                Err(e) => {
+                    warn!("Skipping unreadable cached chunk: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Material names indexed by id, so stored ids can be remapped if the registry changes
+pub fn material_names(registry: &MaterialRegistry) -> Vec<String> {
+    registry.iter().map(|(_, props)| props.name.clone()).collect()
+}
+
+/// Map ids from a file with the given name table onto the currently registered materials
+pub fn material_remap(registry: &MaterialRegistry, names: &[String]) -> impl Fn(Material) -> Material {
+    let table: Vec<Material> = names
+        .iter()
+        .map(|name| {
+            registry.by_name(name).unwrap_or_else(|| {
+                warn!("Unknown material '{}', replacing it with air", name);
+                Material::AIR
+            })
+        })
+        .collect();
+    move |material: Material| table.get(material.0 as usize).copied().unwrap_or(Material::AIR)
+}
+
+/// Load the chunk columns around `center_x` and unload the ones too far away.
+/// Loaded chunks come from the store if they were modified before, otherwise from the generator.
+pub fn update_loaded_chunks(world: &mut PixelWorld, generator: &TerrainGenerator, store: &ChunkStore, center_x: i32) {
+    let rows = (world.height + CHUNK_SIZE - 1) / CHUNK_SIZE;
+
+    let far: Vec<IVec2> = world
+        .chunks()
+        .map(|(pos, _)| pos)
+        .filter(|pos| (pos.x - center_x).abs() > UNLOAD_RADIUS)
+        .collect();
+    for pos in far {
+        let Some(chunk) = world.remove_chunk(pos) else {
+            continue;
+        };
+        if chunk.modified {
+            if let Err(e) = store.save(pos, &chunk) {
+                warn!("Failed to store chunk {}: {}", pos, e);
+            }
+        }
+    }
+
+    for x in center_x - LOAD_RADIUS..=center_x + LOAD_RADIUS {
+        for y in 0..rows {
+            let pos = IVec2::new(x, y);
+            if world.is_chunk_loaded(pos) {
+                continue;
+            }
+            let chunk = match store.load(pos) {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(e)) => {
+                    warn!("Failed to load stored chunk {}, regenerating it: {}", pos, e);
+                    generator.generate_chunk(pos)
+                }
+                None => generator.generate_chunk(pos),
+            };
+            world.insert_chunk(pos, chunk);
+        }
+    }
+}
+
+/// Chunk column under a world-space x position
+pub fn chunk_column_at(world_x: f32) -> i32 {
+    let (pixel_x, _) = WorldService::world_to_pixel(Vec2::new(world_x, 0.0));
+    pixel_x.div_euclid(CHUNK_SIZE)
+}
+
+/// Create the terrain generator and load the chunks around the starting camera position
+pub fn setup_world(
+    mut commands: Commands,
+    seed: Res<WorldSeed>,
+    mut world: ResMut<PixelWorld>,
+    store: Res<ChunkStore>,
+) {
+    info!("Generating world with seed {}", seed.0);
+    let generator = TerrainGenerator::new(seed.0, world.height);
+    store.clear();
+    world.clear();
+    update_loaded_chunks(&mut world, &generator, &store, chunk_column_at(0.0));
+    commands.insert_resource(generator);
+}
+
+/// Stream chunks in and out as the camera moves
+pub fn stream_chunks(
+    mut world: ResMut<PixelWorld>,
+    generator: Res<TerrainGenerator>,
+    store: Res<ChunkStore>,
+    camera: Query<&Transform, With<Camera2d>>,
+    mut last_column: Local<Option<i32>>,
+) {
+    let Ok(camera) = camera.single() else {
+        return;
+    };
+    let center_x = chunk_column_at(camera.translation.x);
+
+    // Loading and unloading only has to happen when the camera moves into another chunk column,
+    // or its own column has gone missing (a save was loaded)
+    let rows = (world.height + CHUNK_SIZE - 1) / CHUNK_SIZE;
+    let needs_update = *last_column != Some(center_x)
+        || (0..rows).any(|y| !world.is_chunk_loaded(IVec2::new(center_x, y)));
+    if needs_update {
+        update_loaded_chunks(&mut world, &generator, &store, center_x);
+        *last_column = Some(center_x);
+    }
+}
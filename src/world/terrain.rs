@@ -1,161 +1,297 @@
 use bevy::prelude::*;
-use rand::Rng;
-use super::{PixelWorld, Material};
-
-pub fn setup_terrain(mut world: ResMut<PixelWorld>) {
-    let mut rng = rand::thread_rng();
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin, Simplex};
+use super::Material;
+use super::biomes::{Biome, BiomeMap};
+use super::chunk::{Chunk, ChunkCanvas, PixelCanvas, CHUNK_SIZE};
+use super::vegetation::{spawn_fiber_bush, TreeSpecies, MAX_TRUNK_WIDTH};
+
+/// Caves never reach closer to the surface than this, leaving thin roofs and overhangs
+const CAVE_MIN_DEPTH: i32 = 4;
+
+/// Rows of solid stone along the bottom of the world
+const BEDROCK_ROWS: i32 = 3;
+
+/// Glowing fungus only grows on cave floors at least this far below the surface
+const FUNGUS_MIN_DEPTH: i32 = 40;
+
+/// Seed for world generation - the same seed always generates the same world
+#[derive(Resource, Clone, Copy)]
+pub struct WorldSeed(pub u64);
+
+impl WorldSeed {
+    /// Seed from `--seed <number>` on the command line, or a random one
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let seed = args
+            .iter()
+            .position(|arg| arg == "--seed")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|value| match value.parse() {
+                Ok(seed) => Some(seed),
+                Err(_) => {
+                    warn!("Invalid --seed '{}', using a random seed", value);
+                    None
+                }
+            });
+        Self(seed.unwrap_or_else(|| rand::thread_rng().gen()))
+    }
+}
 
-    // Create naturally rounded terrain with smooth curves
-    let mut terrain_heights: Vec<i32> = Vec::new();
+/// Pixels either side of a chunk to look for trees whose branches reach into it
+const TREE_REACH: i32 = 64;
 
-    // Generate control points for smooth terrain
-    let num_control_points = rng.gen_range(6..=10);
-    let mut control_points: Vec<(i32, i32)> = vec![];
+/// Width of the cells trees are placed in - at most one tree per cell
+const TREE_CELL: i32 = 40;
 
-    // Generate random control points
-    for i in 0..=num_control_points {
-        let x = (i * 800 / num_control_points) as i32;
-        let y = rng.gen_range(490..=560);
-        control_points.push((x, y));
-    }
+/// Widest a fiber bush can grow, so bushes rooted left of a chunk can still reach it
+const BUSH_REACH: i32 = 25;
 
-    // Interpolate between control points using cosine interpolation for smooth curves
-    for x in 0..800 {
-        // Find the two control points we're between
-        let mut p0_idx = 0;
-        for i in 0..control_points.len() - 1 {
-            if x >= control_points[i].0 && x < control_points[i + 1].0 {
-                p0_idx = i;
-                break;
-            }
-        }
+// Salts so each kind of feature gets its own independent random stream
+const TREE_SALT: u64 = 0x7EE;
+const BUSH_SALT: u64 = 0xB05;
 
-        let p0 = control_points[p0_idx];
-        let p1 = control_points[(p0_idx + 1).min(control_points.len() - 1)];
+/// Mix a seed, a salt and a position into a well-distributed hash (splitmix64)
+pub fn feature_hash(seed: u64, salt: u64, index: i64) -> u64 {
+    let mut z = seed
+        .wrapping_add(salt.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+        .wrapping_add((index as u64).wrapping_mul(0xBF58_476D_1CE4_E5B9));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
 
-        // Cosine interpolation for smooth curves
-        let progress = if p1.0 > p0.0 {
-            (x - p0.0) as f32 / (p1.0 - p0.0) as f32
-        } else {
-            0.0
-        };
+/// Map a hash onto [0, 1)
+pub fn unit_from_hash(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
 
-        // Cosine interpolation creates smooth S-curve
-        let smooth_progress = (1.0 - (progress * std::f32::consts::PI).cos()) / 2.0;
-        let height = (p0.1 as f32 + (p1.1 - p0.1) as f32 * smooth_progress) as i32;
+/// Generates any chunk of the world on demand.
+/// Every feature is placed from hashes of the seed and its position rather than
+/// a shared RNG, so chunks come out the same whatever order they're generated in.
+#[derive(Resource)]
+pub struct TerrainGenerator {
+    seed: u64,
+    height: i32,
+    biomes: BiomeMap,
+    noise: TerrainNoise,
+}
 
-        terrain_heights.push(height);
+impl TerrainGenerator {
+    pub fn new(seed: u64, height: i32) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        Self {
+            seed,
+            height,
+            biomes: BiomeMap::new(seed),
+            noise: TerrainNoise::new(&mut rng),
+        }
     }
 
-    // Fill terrain from surface down to bottom of screen
-    for x in 0..800 {
-        let surface_y = terrain_heights[x];
+    pub fn biome_at(&self, x: i32) -> &'static Biome {
+        self.biomes.biome_at(x)
+    }
 
-        // Fill from surface all the way to the bottom of the screen
-        for y in surface_y..600 {
-            world.set(x as i32, y, Material::Dirt);
-        }
+    /// Row of the topmost ground pixel in a column, before anything is dug out
+    pub fn surface_height(&self, x: i32) -> i32 {
+        self.noise.surface_height(x, &self.biomes.blend_at(x))
     }
 
-    // Ground colliders are now generated dynamically by ground_colliders system
-    // This allows them to update when terrain is dug
+    /// Generate ground, water, trees and bushes for one chunk
+    pub fn generate_chunk(&self, pos: IVec2) -> Chunk {
+        let mut chunk = Chunk::default();
+        let mut canvas = ChunkCanvas { pos, chunk: &mut chunk };
+        let x0 = pos.x * CHUNK_SIZE;
+        let y0 = pos.y * CHUNK_SIZE;
+        let y1 = (y0 + CHUNK_SIZE).min(self.height);
+
+        // Fill each column from the surface down with its biome's layers, caves and ore
+        for x in x0..x0 + CHUNK_SIZE {
+            let surface_y = self.surface_height(x);
+            let biome = self.biomes.biome_at(x);
+            for y in surface_y.max(y0)..y1 {
+                let material = self.noise.underground_material(biome, x, y, surface_y, self.height);
+                canvas.set(x, y, material);
+            }
 
-    // Spawn palm trees with varied sizes
-    for i in 0..6 {
-        let x = 100 + i * 120;
+            // Flooded biomes fill their dips with water
+            if biome.flooded {
+                for y in (biome.surface_level as i32).max(y0)..surface_y.min(y1) {
+                    canvas.set(x, y, Material::WATER);
+                }
+            }
+        }
 
-        // Get ground level at this x position
-        let ground_y = terrain_heights[x as usize];
+        // Trees and bushes rooted in neighbouring chunks can still overhang this one
+        let first_cell = (x0 - TREE_REACH).div_euclid(TREE_CELL);
+        let last_cell = (x0 + CHUNK_SIZE + TREE_REACH).div_euclid(TREE_CELL);
+        for cell in first_cell..=last_cell {
+            if let Some((x, species)) = self.tree_in_cell(cell) {
+                let mut rng = self.feature_rng(TREE_SALT, cell as i64);
+                // Trunks stand on the lowest ground under them, so on a slope they're sunk
+                // into the high side instead of hanging over the low side on one corner
+                let ground_y = (x..x + MAX_TRUNK_WIDTH).map(|x| self.surface_height(x)).max().unwrap();
+                species.spawn(&mut canvas, &mut rng, x, ground_y);
+            }
+        }
 
-        // Random trunk height (60-120 pixels)
-        let trunk_height = rng.gen_range(60..=120);
+        for x in x0 - BUSH_REACH..x0 + CHUNK_SIZE {
+            let hash = feature_hash(self.seed, BUSH_SALT, x as i64);
+            let chance = self.biomes.biome_at(x).bushes_per_100px / 100.0;
+            if unit_from_hash(hash) < chance {
+                let mut rng = StdRng::seed_from_u64(hash);
+                spawn_fiber_bush(&mut canvas, &mut rng, x, self.surface_height(x));
+            }
+        }
 
-        // Random trunk width (8-14 pixels)
-        let trunk_width = rng.gen_range(8..=14);
+        chunk
+    }
 
-        // Palm trunk - narrower and taller than regular trees, growing from ground
-        world.set_rect(x, ground_y - trunk_height, trunk_width, trunk_height, Material::Wood);
+    /// The tree growing in a cell, if any, chosen so trees average out to the biome's spacing
+    fn tree_in_cell(&self, cell: i32) -> Option<(i32, TreeSpecies)> {
+        let start = cell * TREE_CELL;
+        let biome = self.biomes.biome_at(start + TREE_CELL / 2);
+        let species = biome.tree?;
 
-        // Palm fronds - simple leaf crown at the top
-        let top_y = ground_y - trunk_height;
-        let center_x = x + trunk_width / 2;
+        let hash = feature_hash(self.seed, TREE_SALT, cell as i64);
+        let average_spacing = (biome.tree_spacing.0 + biome.tree_spacing.1) as f64 / 2.0;
+        if unit_from_hash(hash) >= TREE_CELL as f64 / average_spacing {
+            return None;
+        }
+        // Keep to the left of the cell so neighbouring trees don't grow into each other
+        let offset = ((hash >> 40) % (TREE_CELL as u64 / 2)) as i32;
+        Some((start + offset, species))
+    }
 
-        // Random frond size (20-30 pixels)
-        let frond_length = rng.gen_range(20..=30);
+    fn feature_rng(&self, salt: u64, index: i64) -> StdRng {
+        StdRng::seed_from_u64(feature_hash(self.seed, salt, index))
+    }
+}
 
-        // Create 5-7 simple fronds radiating from the top
-        let num_fronds = rng.gen_range(5..=7);
-        for j in 0..num_fronds {
-            let angle = (j as f32 / num_fronds as f32) * std::f32::consts::TAU;
-            let angle_offset = rng.gen_range(-0.3..0.3); // Randomness
+/// Noise layers used to shape the terrain, all seeded from the world seed
+struct TerrainNoise {
+    hills: Fbm<Perlin>,
+    bumps: Perlin,
+    caves: Fbm<Perlin>,
+    strata: Perlin,
+    clay_pockets: Simplex,
+    ore: Simplex,
+    fungus: Simplex,
+}
 
-            // Draw each frond as a simple curved shape
-            for dist in 0..frond_length {
-                let progress = dist as f32 / frond_length as f32;
+impl TerrainNoise {
+    fn new(rng: &mut impl Rng) -> Self {
+        Self {
+            hills: Fbm::<Perlin>::new(rng.gen()).set_octaves(4).set_frequency(0.004),
+            bumps: Perlin::new(rng.gen()),
+            caves: Fbm::<Perlin>::new(rng.gen()).set_octaves(3).set_frequency(0.012),
+            strata: Perlin::new(rng.gen()),
+            clay_pockets: Simplex::new(rng.gen()),
+            ore: Simplex::new(rng.gen()),
+            fungus: Simplex::new(rng.gen()),
+        }
+    }
 
-                // Gentle downward curve
-                let curve = progress * progress * 0.4;
+    /// Surface height for a column, as a weighted mix of each contributing biome's profile
+    fn surface_height(&self, x: i32, blend: &[(&Biome, f64)]) -> i32 {
+        let hills = self.hills.get([x as f64, 0.0]);
+        let bumps = self.bumps.get([x as f64 * 0.05, 0.5]);
+        let height: f64 = blend
+            .iter()
+            .map(|(biome, weight)| {
+                weight * (biome.surface_level + hills * biome.hill_height + bumps * biome.roughness)
+            })
+            .sum();
+        height as i32
+    }
 
-                let frond_x = center_x + (angle.cos() * dist as f32) as i32;
-                let frond_y = top_y - 5 + ((angle.sin() + angle_offset) * dist as f32 * curve) as i32;
+    /// Whether a cave has been carved out at this position
+    fn is_cave(&self, x: i32, y: i32, depth: i32) -> bool {
+        if depth < CAVE_MIN_DEPTH {
+            return false;
+        }
+        // Narrow bands around the noise's zero crossing make winding tunnels;
+        // stretching x makes them run more sideways than up and down.
+        // They widen with depth so the deep underground opens into caverns.
+        let value = self.caves.get([x as f64 * 0.6, y as f64]);
+        let width = (0.025 + depth as f64 * 0.0015).min(0.1);
+        value.abs() < width
+    }
 
-                // Width tapers from base to tip (wider in middle)
-                let width_factor = 1.0 - (progress - 0.5).abs() * 2.0; // Peak at middle
-                let width = ((6.0 * width_factor) as i32).max(1);
+    /// Material for an underground pixel `depth` pixels below the surface
+    fn underground_material(&self, biome: &Biome, x: i32, y: i32, surface_y: i32, height: i32) -> Material {
+        let depth = y - surface_y;
+        let (fx, fy) = (x as f64, y as f64);
 
-                // Draw the frond
-                world.set_rect(frond_x - width / 2, frond_y, width, 2, Material::Leaf);
+        if y >= height - BEDROCK_ROWS {
+            return Material::STONE;
+        }
+        if self.is_cave(x, y, depth) {
+            // Glowing fungus grows in patches along deep cave floors
+            let on_floor = !self.is_cave(x, y + 1, depth + 1);
+            if on_floor && depth >= FUNGUS_MIN_DEPTH && self.fungus.get([fx * 0.04, fy * 0.04]) > 0.3 {
+                return Material::GLOW_FUNGUS;
             }
+            return Material::AIR;
         }
 
-        // Add coconuts - small circles near the top of the trunk
-        if rng.gen_bool(0.7) { // 70% chance to have coconuts
-            let num_coconuts = rng.gen_range(2..=4);
-            for _ in 0..num_coconuts {
-                let coconut_x = center_x + rng.gen_range(-8..=8);
-                let coconut_y = top_y + rng.gen_range(0..10);
-                world.set_circle(coconut_x, coconut_y, rng.gen_range(3..=5), Material::Wood);
+        // Layer boundaries wobble so strata don't run perfectly parallel to the surface
+        let mut bottom = 0.0;
+        for (i, &(material, thickness)) in biome.layers.iter().enumerate() {
+            bottom += thickness + self.strata.get([fx * 0.03, i as f64 * 7.5]) * thickness * 0.3;
+            if (depth as f64) < bottom {
+                if material == Material::DIRT && self.clay_pockets.get([fx * 0.06, fy * 0.06]) > 0.6 {
+                    return Material::CLAY;
+                }
+                return material;
             }
         }
+
+        if self.ore.get([fx * 0.07, fy * 0.07]) > biome.ore_threshold {
+            Material::IRON_ORE
+        } else {
+            Material::STONE
+        }
     }
+}
 
-    // Add some sand piles for testing
-    world.set_circle(400, 400, 20, Material::Sand);
-
-    // Spawn fiber bushes scattered on the ground
-    for _ in 0..15 {
-        let bush_x = rng.gen_range(50..750);
-        let bush_y = terrain_heights[bush_x as usize]; // Ground level at this position
-
-        // Random bush size
-        let bush_width = rng.gen_range(15..=25);
-        let bush_height = rng.gen_range(12..=20);
-
-        // Create a rounded bush shape
-        for dy in 0..bush_height {
-            for dx in 0..bush_width {
-                let center_x = bush_width / 2;
-                let center_y = bush_height / 2;
-
-                // Distance from center
-                let dist_x = (dx as i32 - center_x as i32).abs();
-                let dist_y = (dy as i32 - center_y as i32).abs();
-
-                // Create oval/round bush shape
-                let normalized_dist = (dist_x * dist_x) as f32 / (center_x * center_x) as f32
-                    + (dist_y * dist_y) as f32 / (center_y * center_y) as f32;
-
-                // Add some randomness to edges for organic look
-                let threshold = rng.gen_range(0.8..1.2);
-
-                if normalized_dist <= threshold {
-                    world.set(
-                        bush_x + dx as i32,
-                        bush_y - dy as i32,
-                        Material::Fiber
-                    );
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::platform::collections::HashMap;
+
+    /// A stretch of chunks from the sky down to the bottom of the world, with trees
+    fn chunk_positions() -> Vec<IVec2> {
+        (-4..4).flat_map(|x| (0..20).map(move |y| IVec2::new(x, y))).collect()
+    }
+
+    fn generate(seed: u64, positions: &[IVec2]) -> HashMap<IVec2, Vec<Material>> {
+        let generator = TerrainGenerator::new(seed, 640);
+        positions.iter().map(|pos| (*pos, generator.generate_chunk(*pos).pixels)).collect()
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_pixels_in_any_order() {
+        let positions = chunk_positions();
+        let mut reversed = positions.clone();
+        reversed.reverse();
+
+        let first = generate(42, &positions);
+        let again = generate(42, &positions);
+        let backwards = generate(42, &reversed);
+        assert!(first.values().flatten().any(|material| *material == Material::WOOD));
+        for pos in &positions {
+            assert!(first[pos] == again[pos], "chunk {pos} differs between runs");
+            assert!(first[pos] == backwards[pos], "chunk {pos} depends on generation order");
         }
     }
+
+    #[test]
+    fn different_seeds_generate_different_pixels() {
+        let positions = chunk_positions();
+        let first = generate(42, &positions);
+        let other = generate(43, &positions);
+        assert!(positions.iter().any(|pos| first[pos] != other[pos]));
+    }
 }
@@ -0,0 +1,216 @@
+use rand::Rng;
+use super::Material;
+use super::chunk::PixelCanvas;
+
+/// Widest trunk any tree grows
+pub const MAX_TRUNK_WIDTH: i32 = 16;
+
+/// Tree shapes biomes can grow
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreeSpecies {
+    /// Tall bare trunk with a crown of fronds and coconuts
+    Palm,
+    /// Thick trunk with side branches and a broad, layered canopy
+    Jungle,
+    /// Short trunk raised on arching roots, with a wide flat canopy
+    Mangrove,
+    /// Narrow trunk with tiers of foliage tapering to a point
+    Pine,
+}
+
+impl TreeSpecies {
+    /// Grow a tree whose trunk stands on the ground at (x, ground_y)
+    pub fn spawn(&self, world: &mut impl PixelCanvas, rng: &mut impl Rng, x: i32, ground_y: i32) {
+        match self {
+            TreeSpecies::Palm => spawn_palm_tree(world, rng, x, ground_y),
+            TreeSpecies::Jungle => spawn_jungle_tree(world, rng, x, ground_y),
+            TreeSpecies::Mangrove => spawn_mangrove_tree(world, rng, x, ground_y),
+            TreeSpecies::Pine => spawn_pine_tree(world, rng, x, ground_y),
+        }
+    }
+}
+
+/// Draw a thick straight line, used for branches and roots
+fn draw_line(world: &mut impl PixelCanvas, from: (i32, i32), to: (i32, i32), thickness: i32, material: Material) {
+    let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).max(1);
+    for i in 0..=steps {
+        let t = i as f32 / steps as f32;
+        let x = from.0 + ((to.0 - from.0) as f32 * t).round() as i32;
+        let y = from.1 + ((to.1 - from.1) as f32 * t).round() as i32;
+        world.set_rect(x - thickness / 2, y - thickness / 2, thickness, thickness, material);
+    }
+}
+
+/// Palm tree with a trunk growing up from `ground_y`, a crown of fronds and maybe coconuts
+fn spawn_palm_tree(world: &mut impl PixelCanvas, rng: &mut impl Rng, x: i32, ground_y: i32) {
+    // Random trunk height (60-120 pixels)
+    let trunk_height = rng.gen_range(60..=120);
+
+    // Random trunk width (8-14 pixels)
+    let trunk_width = rng.gen_range(8..=14);
+
+    // Palm trunk - narrower and taller than regular trees, growing from ground
+    world.set_rect(x, ground_y - trunk_height, trunk_width, trunk_height, Material::WOOD);
+
+    // Palm fronds - simple leaf crown at the top
+    let top_y = ground_y - trunk_height;
+    let center_x = x + trunk_width / 2;
+
+    // Random frond size (20-30 pixels)
+    let frond_length = rng.gen_range(20..=30);
+
+    // Create 5-7 simple fronds radiating from the top
+    let num_fronds = rng.gen_range(5..=7);
+    for j in 0..num_fronds {
+        let angle = (j as f32 / num_fronds as f32) * std::f32::consts::TAU;
+        let angle_offset = rng.gen_range(-0.3..0.3); // Randomness
+
+        // Draw each frond as a simple curved shape
+        for dist in 0..frond_length {
+            let progress = dist as f32 / frond_length as f32;
+
+            // Gentle downward curve
+            let curve = progress * progress * 0.4;
+
+            let frond_x = center_x + (angle.cos() * dist as f32) as i32;
+            let frond_y = top_y - 5 + ((angle.sin() + angle_offset) * dist as f32 * curve) as i32;
+
+            // Width tapers from base to tip (wider in middle)
+            let width_factor = 1.0 - (progress - 0.5).abs() * 2.0; // Peak at middle
+            let width = ((6.0 * width_factor) as i32).max(1);
+
+            // Draw the frond
+            world.set_rect(frond_x - width / 2, frond_y, width, 2, Material::LEAF);
+        }
+    }
+
+    // Add coconuts - small circles near the top of the trunk
+    spawn_coconuts(world, rng, center_x, top_y);
+}
+
+fn spawn_coconuts(world: &mut impl PixelCanvas, rng: &mut impl Rng, center_x: i32, top_y: i32) {
+    if rng.gen_bool(0.7) { // 70% chance to have coconuts
+        let num_coconuts = rng.gen_range(2..=4);
+        for _ in 0..num_coconuts {
+            let coconut_x = center_x + rng.gen_range(-8..=8);
+            let coconut_y = top_y + rng.gen_range(0..10);
+            world.set_circle(coconut_x, coconut_y, rng.gen_range(3..=5), Material::WOOD);
+        }
+    }
+}
+
+/// Jungle giant: thick trunk, a couple of side branches and overlapping leaf clumps on top
+fn spawn_jungle_tree(world: &mut impl PixelCanvas, rng: &mut impl Rng, x: i32, ground_y: i32) {
+    let trunk_height = rng.gen_range(90..=140);
+    let trunk_width = rng.gen_range(10..=16);
+    let top_y = ground_y - trunk_height;
+    let center_x = x + trunk_width / 2;
+
+    world.set_rect(x, top_y, trunk_width, trunk_height, Material::WOOD);
+
+    // Branches angle up and out from the upper half of the trunk, each ending in a leaf clump
+    let num_branches = rng.gen_range(1..=3);
+    for _ in 0..num_branches {
+        let side = if rng.gen_bool(0.5) { 1 } else { -1 };
+        let start_y = top_y + rng.gen_range(trunk_height / 4..trunk_height / 2);
+        let length = rng.gen_range(15..=28);
+        let end = (center_x + side * length, start_y - length / 2);
+        draw_line(world, (center_x, start_y), end, 3, Material::WOOD);
+        world.set_circle(end.0, end.1 - 4, rng.gen_range(7..=11), Material::LEAF);
+    }
+
+    // Canopy of overlapping clumps
+    let num_clumps = rng.gen_range(3..=5);
+    for _ in 0..num_clumps {
+        let clump_x = center_x + rng.gen_range(-22..=22);
+        let clump_y = top_y + rng.gen_range(-14..=2);
+        world.set_circle(clump_x, clump_y, rng.gen_range(12..=18), Material::LEAF);
+    }
+}
+
+/// Mangrove: trunk held above the mud on arching roots
+fn spawn_mangrove_tree(world: &mut impl PixelCanvas, rng: &mut impl Rng, x: i32, ground_y: i32) {
+    let root_height = rng.gen_range(12..=20);
+    let trunk_height = rng.gen_range(35..=60);
+    let trunk_width = rng.gen_range(6..=9);
+    let base_y = ground_y - root_height;
+    let top_y = base_y - trunk_height;
+    let center_x = x + trunk_width / 2;
+
+    world.set_rect(x, top_y, trunk_width, trunk_height, Material::WOOD);
+
+    // Roots fan out from across the trunk base down into the ground, thick enough
+    // between them to carry the tree. They stay put, so digging out the mud under them
+    // leaves the tree standing on its roots
+    let num_roots = rng.gen_range(3..=5);
+    for i in 0..num_roots {
+        let spread = (i as f32 / (num_roots - 1) as f32 - 0.5) * 2.0;
+        let start_x = x + (trunk_width - 1) * i / (num_roots - 1);
+        let foot_x = center_x + (spread * rng.gen_range(14.0..22.0)) as i32;
+        draw_line(world, (start_x, base_y), (foot_x, ground_y + 2), 4, Material::ROOT);
+    }
+
+    // Wide, flat canopy
+    let canopy_width = rng.gen_range(40..=60);
+    let canopy_height = rng.gen_range(12..=18);
+    for dy in 0..canopy_height {
+        let progress = dy as f32 / canopy_height as f32;
+        // Rounded top, flat bottom
+        let half_width = (canopy_width as f32 / 2.0 * (1.0 - (1.0 - progress).powi(2)).sqrt()) as i32;
+        let row_y = top_y - canopy_height + dy + 4;
+        world.set_rect(center_x - half_width, row_y, half_width * 2, 1, Material::LEAF);
+    }
+}
+
+/// Pine: stacked triangular tiers of foliage up a narrow trunk
+fn spawn_pine_tree(world: &mut impl PixelCanvas, rng: &mut impl Rng, x: i32, ground_y: i32) {
+    let trunk_height = rng.gen_range(50..=90);
+    let trunk_width = rng.gen_range(5..=7);
+    let top_y = ground_y - trunk_height;
+    let center_x = x + trunk_width / 2;
+
+    world.set_rect(x, top_y, trunk_width, trunk_height, Material::WOOD);
+
+    // Tiers get narrower towards the top, leaving bare trunk at the bottom
+    let num_tiers = rng.gen_range(3..=5);
+    let foliage_height = trunk_height * 3 / 4;
+    let tier_height = foliage_height / num_tiers;
+    for tier in 0..num_tiers {
+        let tier_bottom = top_y + foliage_height - tier * tier_height;
+        let base_half_width = 18 - tier * 3 + rng.gen_range(-2..=2);
+        for dy in 0..tier_height + 4 {
+            let half_width = base_half_width * (tier_height + 4 - dy) / (tier_height + 4);
+            world.set_rect(center_x - half_width, tier_bottom - dy, half_width * 2 + 1, 1, Material::LEAF);
+        }
+    }
+}
+
+/// Rounded fiber bush sitting on the ground at (bush_x, bush_y)
+pub fn spawn_fiber_bush(world: &mut impl PixelCanvas, rng: &mut impl Rng, bush_x: i32, bush_y: i32) {
+    // Random bush size
+    let bush_width: i32 = rng.gen_range(15..=25);
+    let bush_height: i32 = rng.gen_range(12..=20);
+
+    // Create a rounded bush shape
+    for dy in 0..bush_height {
+        for dx in 0..bush_width {
+            let center_x = bush_width / 2;
+            let center_y = bush_height / 2;
+
+            // Distance from center
+            let dist_x = (dx - center_x).abs();
+            let dist_y = (dy - center_y).abs();
+
+            // Create oval/round bush shape
+            let normalized_dist = (dist_x * dist_x) as f32 / (center_x * center_x) as f32
+                + (dist_y * dist_y) as f32 / (center_y * center_y) as f32;
+
+            // Add some randomness to edges for organic look
+            let threshold = rng.gen_range(0.8..1.2);
+
+            if normalized_dist <= threshold {
+                world.set(bush_x + dx, bush_y - dy, Material::FIBER);
+            }
+        }
+    }
+}